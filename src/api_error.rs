@@ -0,0 +1,105 @@
+use json::JsonValue;
+use response::Page;
+use utils::WikiRacerError;
+
+// Turns the top-level `error` of a response into the matching error, so a
+// caller can tell a request worth retrying from one that never will work.
+pub fn check_response(base: &JsonValue) -> Result<(), WikiRacerError> {
+    let error = &base["error"];
+    if !error.is_object() {
+        return Ok(());
+    }
+
+    let code = error["code"].as_str().unwrap_or("").to_string();
+    let info = error["info"].as_str().unwrap_or("").to_string();
+
+    Err(match code.as_str() {
+        "maxlag" => WikiRacerError::MaxLag(error["lag"].as_u64()),
+        "ratelimited" => WikiRacerError::RateLimited(info),
+        "badcontinue" => WikiRacerError::BadContinue(info),
+        "badinteger" => WikiRacerError::InvalidPageId(info),
+        _ => WikiRacerError::ApiError(code, info),
+    })
+}
+
+// Collects the `warnings` of a response as "module: text" lines. Both the
+// `*` key of formatversion 1 and the `warnings` key of formatversion 2 are
+// understood.
+pub fn get_warnings(base: &JsonValue) -> Vec<String> {
+    let mut warnings = vec![];
+
+    for (module, warning) in base["warnings"].entries() {
+        let text_opt = warning["*"].as_str().or_else(|| warning["warnings"].as_str()).or_else(|| warning.as_str());
+        if let Some(text) = text_opt {
+            for line in text.lines() {
+                warnings.push(format!("{}: {}", module, line));
+            }
+        }
+    }
+
+    warnings
+}
+
+// Checks a single entry of `query.pages`.
+pub fn check_page(page: &Page) -> Result<(), WikiRacerError> {
+    if page.missing {
+        return Err(WikiRacerError::MissingPage(page.key.clone()));
+    }
+    if let Some(ref reason) = page.invalid_reason {
+        return Err(WikiRacerError::InvalidPageId(format!("{}: {}", page.key, reason)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json;
+    use response::QueryResponse;
+    use std::fs::File;
+    use std::io::Read;
+
+    fn load_saved(name: &str) -> JsonValue {
+        let mut string = String::new();
+        File::open(format!("{}/fixtures/responses/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap().read_to_string(&mut string).unwrap();
+        json::parse(&string).unwrap()
+    }
+
+    #[test]
+    fn maps_error_codes() {
+        match check_response(&load_saved("error_badinteger.json")) {
+            Err(WikiRacerError::InvalidPageId(ref info)) if info.starts_with("Invalid value") => {}
+            other => panic!("Expected an invalid page id, got {:?}", other),
+        }
+        match check_response(&load_saved("error_maxlag.json")) {
+            Err(WikiRacerError::MaxLag(Some(7))) => {}
+            other => panic!("Expected max lag, got {:?}", other),
+        }
+        match check_response(&json::parse(r#"{"error":{"code":"readonly","info":"The wiki is in read-only mode."}}"#).unwrap()) {
+            Err(WikiRacerError::ApiError(ref code, _)) if code == "readonly" => {}
+            other => panic!("Expected an API error, got {:?}", other),
+        }
+        assert!(check_response(&load_saved("warnings.json")).is_ok());
+    }
+
+    #[test]
+    fn collects_warnings_of_both_formats() {
+        assert_eq!(get_warnings(&load_saved("warnings.json")),
+                   vec!["main: Unrecognized parameter: lhlimt.",
+                        "linkshere: \"lhlimit\" may not be over 500 (set to 5000) for users.",
+                        "linkshere: The value passed for \"lhnamespace\" is not valid."]);
+        assert_eq!(get_warnings(&json::parse(r#"{"warnings":{"main":{"warnings":"Unrecognized parameter: lhlimt."}}}"#).unwrap()),
+                   vec!["main: Unrecognized parameter: lhlimt."]);
+        assert!(get_warnings(&load_saved("linkshere_continue.json")).is_empty());
+    }
+
+    #[test]
+    fn checks_missing_and_invalid_pages() {
+        let response = QueryResponse::parse(&load_saved("titles_redirects.json")).unwrap();
+
+        let results: Vec<String> = response.pages.iter().map(|page| format!("{:?}", check_page(page))).collect();
+        assert_eq!(results[0], "Err(MissingPage(\"-1\"))");
+        assert!(results[1].starts_with("Err(InvalidPageId(\"-2: The requested page title is empty"));
+        assert_eq!(results[2], "Ok(())");
+    }
+}
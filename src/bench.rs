@@ -0,0 +1,72 @@
+use querier_x_writer::QuerierToWriter;
+use sqlite_store::SqliteStore;
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::mpsc::sync_channel;
+use std::time::Instant;
+use utils::open_or_create_database;
+use writer::start_writer;
+
+const REPORTS: usize = 10;
+
+// Feeds the writer stage a made up crawl in a scratch database of its own and
+// reports how fast links were written over each tenth of it, so inserts that
+// slow down as the tables grow show in the later tenths.
+pub fn bench_writer(pages: usize, links_per_page: usize) {
+    let database_path = env::temp_dir().join(format!("wikiracer-bench-{}.sqlite3", process::id())).to_string_lossy().into_owned();
+    let _ = fs::remove_file(&database_path);
+
+    let (sender, receiver) = sync_channel::<QuerierToWriter>(4);
+    let writer = start_writer(SqliteStore::new(open_or_create_database(&database_path).unwrap()), receiver);
+
+    let mut random = Xorshift(0x2545F4914F6CDD1D);
+    let report_interval = pages.div_ceil(REPORTS);
+
+    let start = Instant::now();
+    let mut report_start = start;
+
+    for page in 1..pages + 1 {
+        let ext_page_id = page as i64;
+
+        let mut packet_pages = vec![(Some(format!("Page {}", page)), ext_page_id)];
+        let mut packet_links = vec![];
+        for position in 0..links_per_page {
+            let to_ext_page_id = (random.next() % pages as u64 + 1) as i64;
+            packet_pages.push((None, to_ext_page_id));
+            packet_links.push((to_ext_page_id, ext_page_id, Some(position as i64)));
+        }
+
+        sender.send(QuerierToWriter::Packet(packet_pages, packet_links, vec![])).unwrap();
+
+        if page % report_interval == 0 || page == pages {
+            let elapsed = report_start.elapsed().as_secs_f64();
+            let pages_in_report = (page - 1) % report_interval + 1;
+            println!("{} pages, {} links: {:.0} links/s",
+                     page,
+                     page * links_per_page,
+                     (pages_in_report * links_per_page) as f64 / elapsed);
+            report_start = Instant::now();
+        }
+    }
+
+    sender.send(QuerierToWriter::Exit).unwrap();
+    writer.join().unwrap();
+
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("Wrote {} links in {:.2}s, {:.0} links/s", pages * links_per_page, elapsed, (pages * links_per_page) as f64 / elapsed);
+
+    fs::remove_file(&database_path).unwrap();
+}
+
+// Enough randomness to spread links over the pages, and the same every run.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -0,0 +1,85 @@
+use frontier::reclaim_in_flight;
+use rusqlite::Connection;
+use stats::get_stats;
+use time;
+
+#[derive(Debug)]
+pub struct Checkpoint {
+    pub time: String,
+    pub queued: i64,
+    pub done: i64,
+    pub failed: i64,
+}
+
+// Records where a crawl that shut down cleanly left the frontier. Every
+// stage has stopped by then, so nothing is in flight any more and pages
+// still marked that way go back in the queue first.
+pub fn record_checkpoint(conn: &Connection) -> Checkpoint {
+    reclaim_in_flight(conn);
+
+    let stats = get_stats(conn);
+    let checkpoint = Checkpoint {
+        time: time::now_utc().rfc3339().to_string(),
+        queued: stats.queued,
+        done: stats.done,
+        failed: stats.failed,
+    };
+
+    conn.execute("INSERT INTO checkpoint (time, queued, done, failed) VALUES (?1, ?2, ?3, ?4)",
+                 &[&checkpoint.time, &checkpoint.queued, &checkpoint.done, &checkpoint.failed])
+        .unwrap();
+
+    checkpoint
+}
+
+pub fn last_checkpoint(conn: &Connection) -> Option<Checkpoint> {
+    let mut stmt = conn.prepare("SELECT time, queued, done, failed FROM checkpoint ORDER BY checkpoint_id DESC LIMIT 1").unwrap();
+    let mut rows = stmt.query_map(&[], |row| {
+            Checkpoint {
+                time: row.get(0),
+                queued: row.get(1),
+                done: row.get(2),
+                failed: row.get(3),
+            }
+        })
+        .unwrap();
+
+    rows.next().map(|row| row.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontier::{DONE, FAILED, MAX_ATTEMPTS, QUEUED, mark_done, mark_failed, queue_seed, take_queued};
+    use utils::open_or_create_database;
+
+    fn states(conn: &Connection) -> Vec<(i64, String)> {
+        let mut stmt = conn.prepare("SELECT page.ext_page_id, frontier.state FROM frontier JOIN page ON page.page_id = frontier.page_id ORDER BY page.ext_page_id").unwrap();
+        let state_iter = stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap();
+        state_iter.map(|state_result| state_result.unwrap()).collect()
+    }
+
+    #[test]
+    fn reclaims_in_flight_pages_and_records_counts() {
+        let mut conn = open_or_create_database(":memory:").unwrap();
+        assert!(last_checkpoint(&conn).is_none());
+
+        for ext_page_id in 1..6 {
+            queue_seed(&mut conn, ext_page_id);
+        }
+        assert_eq!(take_queued(&mut conn, 4), vec![1, 2, 3, 4]);
+        mark_done(&conn, 1);
+        mark_failed(&conn, 2);
+        // 4 was on its last attempt when the crawl stopped.
+        conn.execute("UPDATE frontier SET attempts = ?1 WHERE page_id = (SELECT page_id FROM page WHERE ext_page_id = 4)", &[&MAX_ATTEMPTS]).unwrap();
+
+        let checkpoint = record_checkpoint(&conn);
+
+        let state = |state: &str| state.to_string();
+        assert_eq!(states(&conn), vec![(1, state(DONE)), (2, state(FAILED)), (3, state(QUEUED)), (4, state(FAILED)), (5, state(QUEUED))]);
+        assert_eq!((checkpoint.queued, checkpoint.done, checkpoint.failed), (2, 1, 2));
+
+        let last = last_checkpoint(&conn).unwrap();
+        assert_eq!((last.time, last.queued, last.done, last.failed), (checkpoint.time, 2, 1, 2));
+    }
+}
@@ -0,0 +1,133 @@
+use main_x_page_id::MainToPageId;
+use std::io::BufRead;
+use std::sync::mpsc::Sender;
+use throttle::RateLimiter;
+
+const HELP: &str = "Commands: status, pause, resume, seed <title>, rate <requests per second>, flush, exit";
+
+// What a console line asks for, before anything is done about it.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Empty,
+    Exit,
+    Packet(MainToPageId),
+    Rate(f64),
+    BadRate(String),
+    Unknown(String),
+}
+
+// Reads commands for a running crawl a line at a time until `exit`, or until
+// the input ends, which leaves the crawl running. `rate` goes straight to the
+// shared rate limiter, everything else to the page id stage.
+pub fn run_console<R: BufRead>(input: R, sender: &Sender<MainToPageId>, rate_limiter: &RateLimiter) {
+    println!("{}", HELP);
+
+    for line_result in input.lines() {
+        let line = match line_result {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        let packet_opt = match parse_command(&line) {
+            Command::Empty => None,
+            Command::Exit => {
+                println!("Exiting");
+                let _ = sender.send(MainToPageId::Exit);
+                return;
+            }
+            Command::Packet(packet_enum) => Some(packet_enum),
+            Command::Rate(requests_per_second) => {
+                rate_limiter.set_rate(requests_per_second);
+                println!("Rate set to {} requests per second", requests_per_second);
+                None
+            }
+            Command::BadRate(rate) => {
+                println!("Rate {:?} is not a number", rate);
+                None
+            }
+            Command::Unknown(line) => {
+                println!("Unknown command {:?}", line);
+                println!("{}", HELP);
+                None
+            }
+        };
+
+        if let Some(packet_enum) = packet_opt {
+            if sender.send(packet_enum).is_err() {
+                return;
+            }
+        }
+    }
+
+    println!("Console closed");
+}
+
+// Reads one console line without acting on it.
+fn parse_command(line: &str) -> Command {
+    let line = line.trim();
+
+    let (command, argument) = match line.find(char::is_whitespace) {
+        Some(space) => (&line[..space], line[space..].trim()),
+        None => (line, ""),
+    };
+
+    match (command, argument) {
+        ("", _) => Command::Empty,
+        ("exit", "") => Command::Exit,
+        ("status", "") => Command::Packet(MainToPageId::Status),
+        ("pause", "") => Command::Packet(MainToPageId::Pause),
+        ("resume", "") => Command::Packet(MainToPageId::Resume),
+        ("flush", "") => Command::Packet(MainToPageId::Flush),
+        ("seed", title) if !title.is_empty() => Command::Packet(MainToPageId::SeedTitle(title.to_string())),
+        ("rate", rate) if !rate.is_empty() => {
+            match rate.parse() {
+                Ok(requests_per_second) => Command::Rate(requests_per_second),
+                Err(_) => Command::BadRate(rate.to_string()),
+            }
+        }
+        _ => Command::Unknown(line.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_command("  "), Command::Empty);
+        assert_eq!(parse_command("exit"), Command::Exit);
+        assert_eq!(parse_command(" status "), Command::Packet(MainToPageId::Status));
+        assert_eq!(parse_command("pause"), Command::Packet(MainToPageId::Pause));
+        assert_eq!(parse_command("resume"), Command::Packet(MainToPageId::Resume));
+        assert_eq!(parse_command("flush"), Command::Packet(MainToPageId::Flush));
+        assert_eq!(parse_command("seed  Albert Einstein "), Command::Packet(MainToPageId::SeedTitle("Albert Einstein".to_string())));
+        assert_eq!(parse_command("rate 2.5"), Command::Rate(2.5));
+        assert_eq!(parse_command("rate\tfast"), Command::BadRate("fast".to_string()));
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_arguments() {
+        for line in &["stop", "seed", "rate", "exit now", "status please", "Pause"] {
+            assert_eq!(parse_command(line), Command::Unknown(line.to_string()));
+        }
+    }
+
+    #[test]
+    fn stops_reading_at_exit() {
+        let (sender, receiver) = channel();
+        run_console(Cursor::new("status\nbogus\nrate 1\nseed A\nexit\npause\n"), &sender, &RateLimiter::new(0.0));
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![MainToPageId::Status, MainToPageId::SeedTitle("A".to_string()), MainToPageId::Exit]);
+    }
+
+    #[test]
+    fn leaves_the_crawl_running_at_the_end_of_input() {
+        let (sender, receiver) = channel();
+        run_console(Cursor::new("pause\nresume"), &sender, &RateLimiter::new(0.0));
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![MainToPageId::Pause, MainToPageId::Resume]);
+    }
+}
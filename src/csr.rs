@@ -0,0 +1,561 @@
+use frontier::DONE;
+use memmap::Mmap;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use store::GraphView;
+use utils::WikiRacerError;
+
+// Compiled graphs start with this, the last byte being the layout version.
+const MAGIC: &[u8; 8] = b"WRCSR\0\0\x01";
+
+const HEADER_LEN: usize = 32;
+
+// In place of a node, for pages that are no redirect and links that went
+// through none.
+const NO_NODE: u32 = u32::MAX;
+
+// Compiles the pages and links into a file `CsrGraph` maps without loading.
+// Pages become nodes in page id order, with the links out of and into each
+// stored compressed sparse row. The sections, in the order `Layout` lists
+// them, are little endian and padded to 8 bytes.
+pub fn export_csr<W: Write>(conn: &Connection, out: &mut W) -> io::Result<()> {
+    let mut nodes: HashMap<i64, u32> = HashMap::new();
+    let mut ext_page_ids: Vec<i64> = vec![];
+    let mut titles: Vec<String> = vec![];
+    let mut crawled: Vec<u8> = vec![];
+
+    {
+        let mut page_stmt = conn.prepare("SELECT page.page_id, page.ext_page_id, IFNULL(page.title, redirect.title), IFNULL(frontier.state = ?1, 0) FROM page
+                                          LEFT JOIN redirect ON redirect.page_id = page.page_id
+                                          LEFT JOIN frontier ON frontier.page_id = page.page_id
+                                          ORDER BY page.page_id")
+            .unwrap();
+
+        let page_iter = page_stmt.query_map(&[&DONE], |row| (row.get(0), row.get(1), row.get(2), row.get(3))).unwrap();
+
+        for page_result in page_iter {
+            let (page_id, ext_page_id_opt, title_opt, is_crawled): (i64, Option<i64>, Option<String>, bool) = page_result.unwrap();
+            if ext_page_ids.len() >= NO_NODE as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "too many pages for a compiled graph"));
+            }
+            nodes.insert(page_id, ext_page_ids.len() as u32);
+            ext_page_ids.push(ext_page_id_opt.unwrap_or(0));
+            titles.push(title_opt.unwrap_or_default());
+            crawled.push(is_crawled as u8);
+        }
+    }
+
+    let node_count = ext_page_ids.len();
+
+    let mut redirect_targets = vec![NO_NODE; node_count];
+    {
+        let mut redirect_stmt = conn.prepare("SELECT page_id, target_page_id FROM redirect").unwrap();
+
+        let redirect_iter = redirect_stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap();
+
+        for redirect_result in redirect_iter {
+            let (page_id, target_page_id): (i64, i64) = redirect_result.unwrap();
+            if let (Some(&node), Some(&target_node)) = (nodes.get(&page_id), nodes.get(&target_page_id)) {
+                redirect_targets[node as usize] = target_node;
+            }
+        }
+    }
+
+    // As (from, to, redirect).
+    let mut links: Vec<(u32, u32, u32)> = vec![];
+    {
+        let mut link_stmt = conn.prepare("SELECT from_page_id, to_page_id, redirect_page_id FROM link").unwrap();
+
+        let link_iter = link_stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap();
+
+        for link_result in link_iter {
+            let (from_page_id, to_page_id, redirect_page_id_opt): (i64, i64, Option<i64>) = link_result.unwrap();
+            if let (Some(&from_node), Some(&to_node)) = (nodes.get(&from_page_id), nodes.get(&to_page_id)) {
+                let redirect_node = redirect_page_id_opt.and_then(|redirect_page_id| nodes.get(&redirect_page_id).cloned()).unwrap_or(NO_NODE);
+                links.push((from_node, to_node, redirect_node));
+            }
+        }
+    }
+
+    let mut title_offsets = Vec::with_capacity(node_count + 1);
+    let mut title_bytes = 0;
+    title_offsets.push(0);
+    for title in &titles {
+        title_bytes += title.len() as u64;
+        title_offsets.push(title_bytes);
+    }
+
+    let mut title_order: Vec<u32> = (0..node_count as u32).collect();
+    title_order.sort_by(|&a, &b| titles[a as usize].cmp(&titles[b as usize]));
+    let mut ext_order: Vec<u32> = (0..node_count as u32).collect();
+    ext_order.sort_by_key(|&node| ext_page_ids[node as usize]);
+
+    let (out_offsets, out_targets, out_redirects) = compress_rows(node_count, links.iter().cloned());
+    let (in_offsets, in_sources, in_redirects) = compress_rows(node_count, links.iter().map(|&(from, to, redirect)| (to, from, redirect)));
+
+    out.write_all(MAGIC)?;
+    write_u64s(out, &[node_count as u64, links.len() as u64, title_bytes])?;
+
+    write_i64s(out, &ext_page_ids)?;
+    write_u32s(out, &redirect_targets)?;
+    write_padded(out, &crawled)?;
+    write_u64s(out, &title_offsets)?;
+    let all_titles: Vec<u8> = titles.into_iter().flat_map(|title| title.into_bytes()).collect();
+    write_padded(out, &all_titles)?;
+    write_u32s(out, &title_order)?;
+    write_u32s(out, &ext_order)?;
+    write_u64s(out, &out_offsets)?;
+    write_u32s(out, &out_targets)?;
+    write_u32s(out, &out_redirects)?;
+    write_u64s(out, &in_offsets)?;
+    write_u32s(out, &in_sources)?;
+    write_u32s(out, &in_redirects)?;
+
+    Ok(())
+}
+
+// Groups (row, column, redirect) by row, returning the offset of every row's
+// first entry, then the columns and the redirects.
+fn compress_rows<I: Iterator<Item = (u32, u32, u32)> + Clone>(row_count: usize, entries: I) -> (Vec<u64>, Vec<u32>, Vec<u32>) {
+    let mut offsets = vec![0u64; row_count + 1];
+    for (row, _, _) in entries.clone() {
+        offsets[row as usize + 1] += 1;
+    }
+    for row in 0..row_count {
+        offsets[row + 1] += offsets[row];
+    }
+
+    let entry_count = offsets[row_count] as usize;
+    let mut columns = vec![0u32; entry_count];
+    let mut redirects = vec![0u32; entry_count];
+    let mut next: Vec<u64> = offsets[..row_count].to_vec();
+    for (row, column, redirect) in entries {
+        let index = next[row as usize] as usize;
+        columns[index] = column;
+        redirects[index] = redirect;
+        next[row as usize] += 1;
+    }
+
+    (offsets, columns, redirects)
+}
+
+fn write_padded<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(bytes)?;
+    out.write_all(&[0u8; 8][..padding(bytes.len())])
+}
+
+fn write_u32s<W: Write>(out: &mut W, values: &[u32]) -> io::Result<()> {
+    let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+    write_padded(out, &bytes)
+}
+
+fn write_u64s<W: Write>(out: &mut W, values: &[u64]) -> io::Result<()> {
+    let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+    write_padded(out, &bytes)
+}
+
+fn write_i64s<W: Write>(out: &mut W, values: &[i64]) -> io::Result<()> {
+    let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+    write_padded(out, &bytes)
+}
+
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+// Where each section starts, worked out from the counts in the header.
+struct Layout {
+    ext_page_ids: usize,
+    redirect_targets: usize,
+    crawled: usize,
+    title_offsets: usize,
+    titles: usize,
+    title_order: usize,
+    ext_order: usize,
+    out_offsets: usize,
+    out_targets: usize,
+    out_redirects: usize,
+    in_offsets: usize,
+    in_sources: usize,
+    in_redirects: usize,
+    len: usize,
+}
+
+impl Layout {
+    // The counts come from the header of a file that may be damaged, so the
+    // sections are added up without overflowing.
+    fn new(node_count: usize, link_count: usize, title_bytes: usize) -> Result<Layout, WikiRacerError> {
+        let too_large = || WikiRacerError::FormatError("Graph file header had counts too large to lay out");
+
+        let mut position = HEADER_LEN;
+        let mut section = |count: usize, width: usize| {
+            let start = position;
+            let len = count.checked_mul(width).ok_or_else(too_large)?;
+            position = len.checked_add(padding(len)).and_then(|padded_len| position.checked_add(padded_len)).ok_or_else(too_large)?;
+            Ok(start)
+        };
+
+        let offset_count = node_count.checked_add(1).ok_or_else(too_large)?;
+        let ext_page_ids = section(node_count, 8)?;
+        let redirect_targets = section(node_count, 4)?;
+        let crawled = section(node_count, 1)?;
+        let title_offsets = section(offset_count, 8)?;
+        let titles = section(title_bytes, 1)?;
+        let title_order = section(node_count, 4)?;
+        let ext_order = section(node_count, 4)?;
+        let out_offsets = section(offset_count, 8)?;
+        let out_targets = section(link_count, 4)?;
+        let out_redirects = section(link_count, 4)?;
+        let in_offsets = section(offset_count, 8)?;
+        let in_sources = section(link_count, 4)?;
+        let in_redirects = section(link_count, 4)?;
+
+        Ok(Layout {
+            ext_page_ids,
+            redirect_targets,
+            crawled,
+            title_offsets,
+            titles,
+            title_order,
+            ext_order,
+            out_offsets,
+            out_targets,
+            out_redirects,
+            in_offsets,
+            in_sources,
+            in_redirects,
+            len: position,
+        })
+    }
+}
+
+// A graph compiled by `export_csr`, mapped into memory rather than read, so
+// opening it only costs a pass over the row offsets however many links there
+// are. Page ids are node numbers.
+pub struct CsrGraph {
+    map: Mmap,
+    node_count: usize,
+    layout: Layout,
+}
+
+impl CsrGraph {
+    pub fn open(path: &Path) -> Result<CsrGraph, WikiRacerError> {
+        let file = File::open(path).map_err(|err| WikiRacerError::IoError(format!("{}: {}", path.display(), err)))?;
+        // Mapped read only. Whoever rewrites the file while it is open gets
+        // what they asked for.
+        let map = unsafe { Mmap::map(&file) }.map_err(|err| WikiRacerError::IoError(format!("{}: {}", path.display(), err)))?;
+
+        if map.len() < HEADER_LEN || &map[..MAGIC.len()] != MAGIC {
+            return Err(WikiRacerError::FormatError("Graph file was not a compiled graph"));
+        }
+
+        let node_count = read_u64(&map, 8) as usize;
+        let link_count = read_u64(&map, 16) as usize;
+        let title_bytes = read_u64(&map, 24) as usize;
+        let layout = Layout::new(node_count, link_count, title_bytes)?;
+        if map.len() != layout.len {
+            return Err(WikiRacerError::FormatError("Graph file was not as long as its header says"));
+        }
+
+        // Offsets become slice bounds on every read, so they are checked once
+        // here instead.
+        if !offsets_in_order(&map, layout.title_offsets, node_count, title_bytes as u64) {
+            return Err(WikiRacerError::FormatError("Graph file had title offsets out of order"));
+        }
+        if !offsets_in_order(&map, layout.out_offsets, node_count, link_count as u64) || !offsets_in_order(&map, layout.in_offsets, node_count, link_count as u64) {
+            return Err(WikiRacerError::FormatError("Graph file had link offsets out of order"));
+        }
+
+        Ok(CsrGraph {
+            map,
+            node_count,
+            layout,
+        })
+    }
+
+    // Whether the file starts like a compiled graph.
+    pub fn is_compiled(path: &Path) -> bool {
+        let mut magic = [0u8; 8];
+        File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == MAGIC
+    }
+
+    fn ext_page_id(&self, node: usize) -> i64 {
+        read_u64(&self.map, self.layout.ext_page_ids + node * 8) as i64
+    }
+
+    fn redirect_target(&self, node: usize) -> Option<usize> {
+        self.node_opt(read_u32(&self.map, self.layout.redirect_targets + node * 4))
+    }
+
+    // Nodes past the last are only found in a damaged file, and taken as none.
+    fn node_opt(&self, node: u32) -> Option<usize> {
+        if node == NO_NODE || node as usize >= self.node_count { None } else { Some(node as usize) }
+    }
+
+    fn is_crawled(&self, node: usize) -> bool {
+        self.map[self.layout.crawled + node] == 1
+    }
+
+    fn title(&self, node: usize) -> &[u8] {
+        let start = read_u64(&self.map, self.layout.title_offsets + node * 8) as usize;
+        let end = read_u64(&self.map, self.layout.title_offsets + (node + 1) * 8) as usize;
+        &self.map[self.layout.titles + start..self.layout.titles + end]
+    }
+
+    // The nodes with this title, found by bisecting the nodes sorted by it.
+    fn nodes_titled(&self, title: &str) -> Vec<usize> {
+        let sorted_node = |index: usize| read_u32(&self.map, self.layout.title_order + index * 4) as usize;
+
+        let first = lower_bound(self.node_count, |index| self.title(sorted_node(index)) < title.as_bytes());
+        (first..self.node_count).map(&sorted_node).take_while(|&node| self.title(node) == title.as_bytes()).collect()
+    }
+
+    fn node_with_ext_page_id(&self, ext_page_id: i64) -> Option<usize> {
+        let sorted_node = |index: usize| read_u32(&self.map, self.layout.ext_order + index * 4) as usize;
+
+        let first = lower_bound(self.node_count, |index| self.ext_page_id(sorted_node(index)) < ext_page_id);
+        Some(first).filter(|&index| index < self.node_count).map(&sorted_node).filter(|&node| self.ext_page_id(node) == ext_page_id)
+    }
+
+    fn row(&self, offsets: usize, columns: usize, redirects: usize, node: usize) -> Vec<(i64, Option<i64>)> {
+        let start = read_u64(&self.map, offsets + node * 8) as usize;
+        let end = read_u64(&self.map, offsets + (node + 1) * 8) as usize;
+
+        (start..end)
+            .filter_map(|index| {
+                let column_opt = self.node_opt(read_u32(&self.map, columns + index * 4));
+                let redirect_opt = self.node_opt(read_u32(&self.map, redirects + index * 4)).map(|redirect| redirect as i64);
+                column_opt.map(|column| (column as i64, redirect_opt))
+            })
+            .collect()
+    }
+}
+
+impl GraphView for CsrGraph {
+    fn find_crawled_page(&self, title_or_ext_page_id: &str) -> Option<i64> {
+        let mut named = self.nodes_titled(title_or_ext_page_id);
+        named.extend(title_or_ext_page_id.parse().ok().and_then(|ext_page_id| self.node_with_ext_page_id(ext_page_id)));
+
+        let redirect_target_opt = named.iter().filter_map(|&node| self.redirect_target(node)).find(|&target| self.is_crawled(target));
+        redirect_target_opt.or_else(|| named.into_iter().find(|&node| self.is_crawled(node))).map(|node| node as i64)
+    }
+
+    fn links_from(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        self.row(self.layout.out_offsets, self.layout.out_targets, self.layout.out_redirects, page_id as usize)
+    }
+
+    fn links_to(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        self.row(self.layout.in_offsets, self.layout.in_sources, self.layout.in_redirects, page_id as usize)
+    }
+
+    fn page_label(&self, page_id: i64) -> String {
+        let title = self.title(page_id as usize);
+        if title.is_empty() {
+            self.ext_page_id(page_id as usize).to_string()
+        } else {
+            String::from_utf8_lossy(title).into_owned()
+        }
+    }
+
+    fn redirect_title(&self, redirect_page_id: i64) -> String {
+        self.page_label(redirect_page_id)
+    }
+}
+
+// The first index in `0..len` for which `is_before` is false, given it is
+// true for a run of indexes at the start and false for the rest.
+fn lower_bound<F: Fn(usize) -> bool>(len: usize, is_before: F) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if is_before(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+// Whether the `row_count + 1` offsets at `offsets` start at 0, never go back
+// and end at `end`, the length of what they index.
+fn offsets_in_order(bytes: &[u8], offsets: usize, row_count: usize, end: u64) -> bool {
+    let mut previous = 0;
+    for row in 0..row_count + 1 {
+        let offset = read_u64(bytes, offsets + row * 8);
+        if offset < previous || (row == 0 && offset != 0) {
+            return false;
+        }
+        previous = offset;
+    }
+    previous == end
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solver::{PathResult, find_path};
+    use sqlite_store::SqliteStore;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use store::GraphStore;
+    use utils::open_or_create_database;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("wikiracer-csr-{}-{}", process::id(), name))
+    }
+
+    // A has a link through the redirect R to B, D was never crawled and 7 only
+    // ever seen as an id.
+    fn build_database(path: &Path) {
+        let mut store = SqliteStore::new(open_or_create_database(path.to_str().unwrap()).unwrap());
+        for &(title_opt, ext_page_id) in &[(Some("A"), 1), (None, 2), (Some("B"), 3), (Some("C"), 4), (None, 5), (Some("E"), 6), (None, 7)] {
+            store.upsert_page(title_opt.map(str::to_string), ext_page_id);
+        }
+        store.add_redirect(2, "R".to_string(), 3);
+        for &(to, from) in &[(2, 1), (4, 3), (1, 4), (5, 1), (3, 6), (7, 6), (1, 5)] {
+            store.add_link(to, from, None);
+        }
+    }
+
+    fn write_graph(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        path
+    }
+
+    fn open_bytes(name: &str, bytes: &[u8]) -> Result<CsrGraph, WikiRacerError> {
+        let path = write_graph(name, bytes);
+        let graph_result = CsrGraph::open(&path);
+        fs::remove_file(&path).unwrap();
+        graph_result
+    }
+
+    // Page ids differ between the two, so links are compared by label.
+    fn labeled_links<G: GraphView>(graph: &G, links: Vec<(i64, Option<i64>)>) -> Vec<(String, Option<String>)> {
+        let mut labeled: Vec<(String, Option<String>)> =
+            links.into_iter().map(|(page_id, redirect_page_id_opt)| (graph.page_label(page_id), redirect_page_id_opt.map(|redirect_page_id| graph.redirect_title(redirect_page_id)))).collect();
+        labeled.sort();
+        labeled
+    }
+
+    fn exported_bytes() -> Vec<u8> {
+        let database_path = temp_path("round-trip.sqlite3");
+        let _ = fs::remove_file(&database_path);
+        build_database(&database_path);
+
+        let conn = open_or_create_database(database_path.to_str().unwrap()).unwrap();
+        let mut bytes = vec![];
+        export_csr(&conn, &mut bytes).unwrap();
+        fs::remove_file(&database_path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn reads_back_what_the_database_holds() {
+        let database_path = temp_path("compare.sqlite3");
+        let _ = fs::remove_file(&database_path);
+        build_database(&database_path);
+
+        let conn = open_or_create_database(database_path.to_str().unwrap()).unwrap();
+        let mut bytes = vec![];
+        export_csr(&conn, &mut bytes).unwrap();
+        let store = SqliteStore::new(conn);
+
+        let graph_path = write_graph("compare.csr", &bytes);
+        assert!(CsrGraph::is_compiled(&graph_path));
+        let graph = CsrGraph::open(&graph_path).unwrap();
+
+        let names = ["A", "B", "C", "E", "R", "D", "1", "2", "3", "5", "7", "Nowhere"];
+        for from in &names {
+            assert_eq!(graph.find_crawled_page(from).map(|page_id| graph.page_label(page_id)),
+                       store.find_crawled_page(from).map(|page_id| store.page_label(page_id)),
+                       "{}",
+                       from);
+
+            if let (Some(graph_page_id), Some(store_page_id)) = (graph.find_crawled_page(from), store.find_crawled_page(from)) {
+                assert_eq!(labeled_links(&graph, graph.links_to(graph_page_id)), labeled_links(&store, store.links_to(store_page_id)), "{}", from);
+                assert_eq!(labeled_links(&graph, graph.links_from(graph_page_id)), labeled_links(&store, store.links_from(store_page_id)), "{}", from);
+            }
+
+            for to in &names {
+                assert_eq!(format!("{:?}", find_path(&graph, from, to)), format!("{:?}", find_path(&store, from, to)), "{} to {}", from, to);
+            }
+        }
+
+        match find_path(&graph, "E", "C") {
+            PathResult::Found(ref steps) => assert_eq!(steps.iter().map(|step| step.to_string()).collect::<Vec<String>>(), vec!["E", "B", "C"]),
+            ref other => panic!("Expected a path, got {:?}", other),
+        }
+
+        drop(graph);
+        fs::remove_file(&graph_path).unwrap();
+        fs::remove_file(&database_path).unwrap();
+    }
+
+    fn set_u64(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn expect_format_error(name: &str, bytes: &[u8]) {
+        match open_bytes(name, bytes) {
+            Err(WikiRacerError::FormatError(_)) => {}
+            Err(err) => panic!("Expected a format error for {}, got {:?}", name, err),
+            Ok(_) => panic!("Expected a format error for {}, the graph opened", name),
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let bytes = exported_bytes();
+        assert!(open_bytes("intact", &bytes).is_ok());
+
+        let node_count = read_u64(&bytes, 8) as usize;
+        let link_count = read_u64(&bytes, 16) as usize;
+        let title_bytes = read_u64(&bytes, 24) as usize;
+        let layout = Layout::new(node_count, link_count, title_bytes).unwrap();
+
+        expect_format_error("short", &bytes[..HEADER_LEN - 1]);
+        expect_format_error("truncated", &bytes[..bytes.len() - 8]);
+
+        for &(offset, count) in &[(8, u64::MAX), (8, u64::MAX / 8), (16, u64::MAX / 4 + 1), (24, u64::MAX - 8)] {
+            let mut damaged = bytes.clone();
+            set_u64(&mut damaged, offset, count);
+            expect_format_error("overflowing", &damaged);
+        }
+
+        let mut damaged = bytes.clone();
+        set_u64(&mut damaged, layout.title_offsets + 8, title_bytes as u64 + 1);
+        expect_format_error("title-past-end", &damaged);
+
+        let mut damaged = bytes.clone();
+        set_u64(&mut damaged, layout.out_offsets, 1);
+        expect_format_error("links-not-from-zero", &damaged);
+
+        let mut damaged = bytes.clone();
+        set_u64(&mut damaged, layout.in_offsets + 8, link_count as u64);
+        set_u64(&mut damaged, layout.in_offsets + 16, 0);
+        expect_format_error("links-going-back", &damaged);
+
+        let mut damaged = bytes.clone();
+        set_u64(&mut damaged, layout.out_offsets + node_count * 8, link_count as u64 - 1);
+        expect_format_error("links-short-of-end", &damaged);
+    }
+}
@@ -0,0 +1,52 @@
+use rusqlite::Connection;
+use std::io::{self, Write};
+
+// Pages are written before redirects and links so that an import can replay
+// the file in order through the writer, which drops links to pages it has not
+// seen yet. Pages without an ext page id cannot be named in the file, so they
+// are left out along with their links.
+pub fn export_graph<W: Write>(conn: &Connection, out: &mut W) -> io::Result<()> {
+    let mut page_stmt = conn.prepare("SELECT ext_page_id, title FROM page WHERE ext_page_id IS NOT NULL ORDER BY page_id").unwrap();
+
+    let page_iter = page_stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap();
+
+    for page_result in page_iter {
+        let (ext_page_id, title_opt): (i64, Option<String>) = page_result.unwrap();
+        writeln!(out, "page\t{}\t{}", ext_page_id, title_opt.unwrap_or_default())?;
+    }
+
+    let mut redirect_stmt = conn.prepare("SELECT page.ext_page_id, redirect.title, target_page.ext_page_id FROM redirect
+                                          JOIN page ON page.page_id = redirect.page_id
+                                          JOIN page AS target_page ON target_page.page_id = redirect.target_page_id
+                                          WHERE page.ext_page_id IS NOT NULL AND target_page.ext_page_id IS NOT NULL
+                                          ORDER BY redirect.page_id")
+        .unwrap();
+
+    let redirect_iter = redirect_stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap();
+
+    for redirect_result in redirect_iter {
+        let (ext_page_id, title, target_ext_page_id): (i64, String, i64) = redirect_result.unwrap();
+        writeln!(out, "redirect\t{}\t{}\t{}", ext_page_id, title, target_ext_page_id)?;
+    }
+
+    // A link through a redirect is written as the link on the page, to the
+    // redirect, which the writer resolves again on import.
+    let mut link_stmt = conn.prepare("SELECT to_page.ext_page_id, from_page.ext_page_id, link.position FROM link
+                                      JOIN page AS to_page ON to_page.page_id = IFNULL(link.redirect_page_id, link.to_page_id)
+                                      JOIN page AS from_page ON from_page.page_id = link.from_page_id
+                                      WHERE to_page.ext_page_id IS NOT NULL AND from_page.ext_page_id IS NOT NULL
+                                      ORDER BY link.link_id")
+        .unwrap();
+
+    let link_iter = link_stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap();
+
+    for link_result in link_iter {
+        let (to_ext_page_id, from_ext_page_id, position_opt): (i64, i64, Option<i64>) = link_result.unwrap();
+        match position_opt {
+            Some(position) => writeln!(out, "link\t{}\t{}\t{}", to_ext_page_id, from_ext_page_id, position)?,
+            None => writeln!(out, "link\t{}\t{}", to_ext_page_id, from_ext_page_id)?,
+        }
+    }
+
+    Ok(())
+}
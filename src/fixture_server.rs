@@ -0,0 +1,99 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use transport::{FixtureTransport, Transport};
+
+// A stand-in for `api.php` that serves fixtures over plain HTTP, so the
+// crawler can run end to end against `HttpTransport` without the network.
+// Every connection gets a thread, clients keep theirs open between queries.
+pub fn start_fixture_server(listener: TcpListener, fixtures: FixtureTransport) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let fixtures = Arc::new(fixtures);
+
+        for stream_result in listener.incoming() {
+            match stream_result {
+                Ok(stream) => {
+                    let fixtures = fixtures.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = serve(stream, &fixtures) {
+                            println!("Fixture server connection failed: {:?}", err);
+                        }
+                    });
+                }
+                Err(err) => println!("Fixture server accept failed: {:?}", err),
+            }
+        }
+    })
+}
+
+fn serve(stream: TcpStream, fixtures: &FixtureTransport) -> ::std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line)? == 0 {
+            return Ok(());
+        }
+
+        let mut keep_alive = request_line.trim().ends_with("HTTP/1.1");
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if header.to_lowercase().starts_with("connection:") && header.to_lowercase().contains("close") {
+                keep_alive = false;
+            }
+        }
+
+        let target = request_line.split(' ').nth(1).unwrap_or("");
+        let query = target.split_once('?').map_or("", |(_, query)| query);
+
+        let (status, body) = match fixtures.fetch(query) {
+            Ok(body) => ("200 OK", body),
+            Err(err) => ("404 Not Found", err.to_string()),
+        };
+
+        write!(writer,
+               "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+               status,
+               body.len(),
+               body)?;
+        writer.flush()?;
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use transport::HttpTransport;
+    use utils::WikiRacerError;
+
+    #[test]
+    fn serves_fixtures_to_http_transport() {
+        let fixtures = FixtureTransport::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/crawl"))).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        start_fixture_server(listener, fixtures.clone());
+
+        let transport = HttpTransport::new(&format!("http://{}/w/api.php", address));
+
+        // Escaped the way a recorder may have, twice over the same connection.
+        let query = "action=query&format=json&pageids=1&prop=linkshere&lhprop=pageid%7Ctitle%7Credirect&lhlimit=500&lhnamespace=0";
+        for _ in 0..2 {
+            assert_eq!(transport.fetch(query).unwrap(), fixtures.fetch(query).unwrap());
+        }
+
+        match transport.fetch("action=query&format=json&pageids=7") {
+            Err(WikiRacerError::HttpError(404, None)) => {}
+            other => panic!("Expected a 404, got {:?}", other),
+        }
+    }
+}
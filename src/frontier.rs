@@ -0,0 +1,111 @@
+use rusqlite::Connection;
+
+pub const QUEUED: &str = "queued";
+pub const IN_FLIGHT: &str = "in_flight";
+pub const DONE: &str = "done";
+pub const FAILED: &str = "failed";
+
+pub const MAX_ATTEMPTS: i64 = 3;
+
+// Pages left in flight by a killed process are handed out again, unless they
+// have already used up their attempts.
+pub fn reclaim_in_flight(conn: &Connection) {
+    conn.execute("UPDATE frontier SET state = CASE WHEN attempts >= ?1 THEN ?2 ELSE ?3 END WHERE state = ?4",
+                 &[&MAX_ATTEMPTS, &FAILED, &QUEUED, &IN_FLIGHT])
+        .unwrap();
+}
+
+// The title of a seed is left for the crawl to write, since a page with a
+// title counts as crawled once exported.
+pub fn queue_seed(conn: &mut Connection, ext_page_id: i64) {
+    let transaction = conn.transaction().unwrap();
+
+    transaction.execute("INSERT INTO page (ext_page_id) SELECT ?1 WHERE NOT EXISTS (SELECT 1 FROM page WHERE ext_page_id = ?1)", &[&ext_page_id])
+        .unwrap();
+    transaction.execute("INSERT OR IGNORE INTO frontier (page_id, state, depth, attempts) SELECT page_id, ?1, 0, 0 FROM page WHERE ext_page_id = ?2",
+                        &[&QUEUED, &ext_page_id])
+        .unwrap();
+    transaction.execute("UPDATE frontier SET state = ?1, depth = 0, attempts = 0 WHERE state = ?2 AND page_id = (SELECT page_id FROM page WHERE ext_page_id = ?3)",
+                        &[&QUEUED, &FAILED, &ext_page_id])
+        .unwrap();
+
+    transaction.commit().unwrap();
+}
+
+// Hands out the shallowest queued pages, marking them in flight so a restart
+// knows they were never written. Pages without an ext page id cannot be asked
+// for, so they are never handed out.
+pub fn take_queued(conn: &mut Connection, max_in_flight: usize) -> Vec<i64> {
+    let transaction = conn.transaction().unwrap();
+
+    let in_flight: i64 = transaction.query_row("SELECT COUNT(*) FROM frontier WHERE state = ?1", &[&IN_FLIGHT], |row| row.get(0)).unwrap();
+    let limit = max_in_flight as i64 - in_flight;
+
+    let mut taken = vec![];
+
+    if limit > 0 {
+        {
+            let mut stmt = transaction.prepare("SELECT frontier.page_id, page.ext_page_id FROM frontier
+                                                JOIN page ON page.page_id = frontier.page_id
+                                                WHERE frontier.state = ?1 AND page.ext_page_id IS NOT NULL
+                                                ORDER BY frontier.depth, frontier.page_id
+                                                LIMIT ?2")
+                .unwrap();
+
+            let queued_iter = stmt.query_map(&[&QUEUED, &limit], |row| (row.get(0), row.get(1))).unwrap();
+
+            for queued_result in queued_iter {
+                let (page_id, ext_page_id): (i64, i64) = queued_result.unwrap();
+                taken.push((page_id, ext_page_id));
+            }
+        }
+
+        for &(page_id, _) in &taken {
+            transaction.execute("UPDATE frontier SET state = ?1, attempts = attempts + 1 WHERE page_id = ?2", &[&IN_FLIGHT, &page_id])
+                .unwrap();
+        }
+    }
+
+    transaction.commit().unwrap();
+
+    taken.into_iter().map(|(_, ext_page_id)| ext_page_id).collect()
+}
+
+pub fn mark_done(conn: &Connection, ext_page_id: i64) {
+    conn.prepare_cached("INSERT OR IGNORE INTO frontier (page_id, state, depth, attempts) SELECT page_id, ?1, 0, 0 FROM page WHERE ext_page_id = ?2")
+        .unwrap()
+        .execute(&[&DONE, &ext_page_id])
+        .unwrap();
+    conn.prepare_cached("UPDATE frontier SET state = ?1 WHERE page_id = (SELECT page_id FROM page WHERE ext_page_id = ?2)")
+        .unwrap()
+        .execute(&[&DONE, &ext_page_id])
+        .unwrap();
+}
+
+// A page discovered through a link sits one step further from the seeds than
+// the crawled page at the other end of that link.
+pub fn queue_discovered(conn: &Connection, crawled_ext_page_id: i64, discovered_ext_page_id: i64) {
+    conn.prepare_cached("INSERT OR IGNORE INTO frontier (page_id, state, depth, attempts)
+                         SELECT discovered_page.page_id, ?1, IFNULL(crawled_frontier.depth, 0) + 1, 0 FROM page AS discovered_page, page AS crawled_page
+                         LEFT JOIN frontier AS crawled_frontier ON crawled_frontier.page_id = crawled_page.page_id
+                         WHERE discovered_page.ext_page_id = ?2 AND crawled_page.ext_page_id = ?3")
+        .unwrap()
+        .execute(&[&QUEUED, &discovered_ext_page_id, &crawled_ext_page_id])
+        .unwrap();
+}
+
+pub fn requeue_or_fail(conn: &Connection, ext_page_id: i64) {
+    conn.prepare_cached("UPDATE frontier SET state = CASE WHEN attempts >= ?1 THEN ?2 ELSE ?3 END
+                         WHERE page_id = (SELECT page_id FROM page WHERE ext_page_id = ?4)")
+        .unwrap()
+        .execute(&[&MAX_ATTEMPTS, &FAILED, &QUEUED, &ext_page_id])
+        .unwrap();
+}
+
+// Pages the API says are gone will not come back by asking again.
+pub fn mark_failed(conn: &Connection, ext_page_id: i64) {
+    conn.prepare_cached("UPDATE frontier SET state = ?1 WHERE page_id = (SELECT page_id FROM page WHERE ext_page_id = ?2)")
+        .unwrap()
+        .execute(&[&FAILED, &ext_page_id])
+        .unwrap();
+}
@@ -0,0 +1,302 @@
+use frontier::DONE;
+use querier::LinkDirection;
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+use store::GraphView;
+use utils::WikiRacerError;
+
+// File formats for drawing or analysing the graph in other tools, as
+// opposed to the export that import reads back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Gexf,
+    Csv,
+}
+
+impl FromStr for GraphFormat {
+    type Err = WikiRacerError;
+
+    fn from_str(format: &str) -> Result<GraphFormat, WikiRacerError> {
+        match format {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "gexf" => Ok(GraphFormat::Gexf),
+            "csv" => Ok(GraphFormat::Csv),
+            _ => Err(WikiRacerError::FormatError("Graph format was not dot, graphml, gexf or csv")),
+        }
+    }
+}
+
+// The page ids within `depth` links of a page, following links the given
+// way, or `None` if the page is not crawled.
+pub fn ego_pages<G: GraphView>(graph: &G, center: &str, depth: usize, direction: LinkDirection) -> Option<HashSet<i64>> {
+    let center_page_id = graph.find_crawled_page(center)?;
+
+    let mut pages = HashSet::new();
+    pages.insert(center_page_id);
+    let mut level = vec![center_page_id];
+
+    for _ in 0..depth {
+        let mut next_level = vec![];
+        for page_id in level {
+            let mut neighbors = vec![];
+            if direction != LinkDirection::Backlinks {
+                neighbors.extend(graph.links_from(page_id));
+            }
+            if direction != LinkDirection::Links {
+                neighbors.extend(graph.links_to(page_id));
+            }
+            for (neighbor, _) in neighbors {
+                if pages.insert(neighbor) {
+                    next_level.push(neighbor);
+                }
+            }
+        }
+        level = next_level;
+    }
+
+    Some(pages)
+}
+
+// Writes the pages as nodes labelled by title and the links between them as
+// edges, only those in `pages_opt` if given. Redirects are left out, links
+// already point at their targets bar a few stored before that was known.
+pub fn export_drawing<W: Write>(conn: &Connection, format: GraphFormat, pages_opt: Option<&HashSet<i64>>, out: &mut W) -> io::Result<()> {
+    let is_kept = |page_id: i64| pages_opt.is_none_or(|pages| pages.contains(&page_id));
+
+    match format {
+        GraphFormat::Dot => writeln!(out, "digraph wikiracer {{")?,
+        GraphFormat::GraphMl => {
+            writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+            writeln!(out, "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>")?;
+            writeln!(out, "  <key id=\"ext_page_id\" for=\"node\" attr.name=\"ext_page_id\" attr.type=\"long\"/>")?;
+            writeln!(out, "  <key id=\"crawled\" for=\"node\" attr.name=\"crawled\" attr.type=\"boolean\"/>")?;
+            writeln!(out, "  <graph edgedefault=\"directed\">")?;
+        }
+        GraphFormat::Gexf => {
+            writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(out, "<gexf xmlns=\"http://gexf.net/1.2\" version=\"1.2\">")?;
+            writeln!(out, "  <graph mode=\"static\" defaultedgetype=\"directed\">")?;
+            writeln!(out, "    <attributes class=\"node\">")?;
+            writeln!(out, "      <attribute id=\"ext_page_id\" title=\"ext_page_id\" type=\"long\"/>")?;
+            writeln!(out, "      <attribute id=\"crawled\" title=\"crawled\" type=\"boolean\"/>")?;
+            writeln!(out, "    </attributes>")?;
+            writeln!(out, "    <nodes>")?;
+        }
+        // An edge list has no nodes of its own, so the labels go on every
+        // edge.
+        GraphFormat::Csv => writeln!(out, "source,target,source_label,target_label")?,
+    }
+
+    let mut page_stmt = conn.prepare("SELECT page.page_id, page.ext_page_id, page.title, IFNULL(frontier.state = ?1, 0) FROM page
+                                      LEFT JOIN frontier ON frontier.page_id = page.page_id
+                                      WHERE page.page_id NOT IN (SELECT page_id FROM redirect)
+                                      ORDER BY page.page_id")
+        .unwrap();
+
+    let page_iter = page_stmt.query_map(&[&DONE], |row| (row.get(0), row.get(1), row.get(2), row.get(3))).unwrap();
+
+    for page_result in page_iter {
+        let (page_id, ext_page_id_opt, title_opt, crawled): (i64, Option<i64>, Option<String>, bool) = page_result.unwrap();
+        if !is_kept(page_id) {
+            continue;
+        }
+        let label = page_label(title_opt, ext_page_id_opt);
+
+        match format {
+            GraphFormat::Dot => writeln!(out, "  {} [label=\"{}\"];", page_id, escape_dot(&label))?,
+            GraphFormat::GraphMl => {
+                writeln!(out, "    <node id=\"n{}\">", page_id)?;
+                writeln!(out, "      <data key=\"label\">{}</data>", escape_xml(&label))?;
+                if let Some(ext_page_id) = ext_page_id_opt {
+                    writeln!(out, "      <data key=\"ext_page_id\">{}</data>", ext_page_id)?;
+                }
+                writeln!(out, "      <data key=\"crawled\">{}</data>", crawled)?;
+                writeln!(out, "    </node>")?;
+            }
+            GraphFormat::Gexf => {
+                writeln!(out, "      <node id=\"{}\" label=\"{}\">", page_id, escape_xml(&label))?;
+                writeln!(out, "        <attvalues>")?;
+                if let Some(ext_page_id) = ext_page_id_opt {
+                    writeln!(out, "          <attvalue for=\"ext_page_id\" value=\"{}\"/>", ext_page_id)?;
+                }
+                writeln!(out, "          <attvalue for=\"crawled\" value=\"{}\"/>", crawled)?;
+                writeln!(out, "        </attvalues>")?;
+                writeln!(out, "      </node>")?;
+            }
+            GraphFormat::Csv => (),
+        }
+    }
+
+    if format == GraphFormat::Gexf {
+        writeln!(out, "    </nodes>")?;
+        writeln!(out, "    <edges>")?;
+    }
+
+    let mut link_stmt = conn.prepare("SELECT link.from_page_id, link.to_page_id, from_page.title, from_page.ext_page_id, to_page.title, to_page.ext_page_id FROM link
+                                      JOIN page AS from_page ON from_page.page_id = link.from_page_id
+                                      JOIN page AS to_page ON to_page.page_id = link.to_page_id
+                                      WHERE link.to_page_id NOT IN (SELECT page_id FROM redirect) AND link.from_page_id NOT IN (SELECT page_id FROM redirect)
+                                      ORDER BY link.link_id")
+        .unwrap();
+
+    let link_iter = link_stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4), row.get(5))).unwrap();
+
+    let mut edge_count = 0;
+    for link_result in link_iter {
+        let (from_page_id, to_page_id, from_title_opt, from_ext_page_id_opt, to_title_opt, to_ext_page_id_opt): (i64, i64, Option<String>, Option<i64>, Option<String>, Option<i64>) = link_result.unwrap();
+        if !is_kept(from_page_id) || !is_kept(to_page_id) {
+            continue;
+        }
+
+        match format {
+            GraphFormat::Dot => writeln!(out, "  {} -> {};", from_page_id, to_page_id)?,
+            GraphFormat::GraphMl => writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"/>", from_page_id, to_page_id)?,
+            GraphFormat::Gexf => writeln!(out, "      <edge id=\"{}\" source=\"{}\" target=\"{}\"/>", edge_count, from_page_id, to_page_id)?,
+            GraphFormat::Csv => {
+                writeln!(out,
+                         "{},{},{},{}",
+                         from_page_id,
+                         to_page_id,
+                         escape_csv(&page_label(from_title_opt, from_ext_page_id_opt)),
+                         escape_csv(&page_label(to_title_opt, to_ext_page_id_opt)))?
+            }
+        }
+        edge_count += 1;
+    }
+
+    match format {
+        GraphFormat::Dot => writeln!(out, "}}")?,
+        GraphFormat::GraphMl => {
+            writeln!(out, "  </graph>")?;
+            writeln!(out, "</graphml>")?;
+        }
+        GraphFormat::Gexf => {
+            writeln!(out, "    </edges>")?;
+            writeln!(out, "  </graph>")?;
+            writeln!(out, "</gexf>")?;
+        }
+        GraphFormat::Csv => (),
+    }
+
+    Ok(())
+}
+
+// Pages not crawled yet may have no title, and pages from before ext page ids
+// were numbers may have no ext page id, but never neither.
+fn page_label(title_opt: Option<String>, ext_page_id_opt: Option<i64>) -> String {
+    title_opt.or_else(|| ext_page_id_opt.map(|ext_page_id| ext_page_id.to_string())).unwrap_or_default()
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+fn escape_csv(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory_store::MemoryStore;
+    use store::GraphStore;
+    use utils::open_or_create_database;
+    use xml::reader::EventReader;
+
+    const TITLES: &[&str] = &["Tom & Jerry", "\"Weird Al\" Yankovic", "Washington, D.C.", "<i>Italic</i> & 'quoted'"];
+
+    // Each title links to the next, with the first crawled and the rest not.
+    fn drawing(format: GraphFormat, pages_opt: Option<&HashSet<i64>>) -> String {
+        let conn = open_or_create_database(":memory:").unwrap();
+        for (index, title) in TITLES.iter().enumerate() {
+            let page_id = index as i64 + 1;
+            conn.execute("INSERT INTO page (page_id, title, ext_page_id) VALUES (?1, ?2, ?3)", &[&page_id, title, &(page_id * 10)]).unwrap();
+            conn.execute("INSERT INTO frontier (page_id, state, depth, attempts) VALUES (?1, ?2, 0, 0)", &[&page_id, &if page_id == 1 { DONE } else { "queued" }])
+                .unwrap();
+            if page_id > 1 {
+                conn.execute("INSERT INTO link (to_page_id, from_page_id, length) VALUES (?1, ?2, 1)", &[&page_id, &(page_id - 1)]).unwrap();
+            }
+        }
+
+        let mut out = vec![];
+        export_drawing(&conn, format, pages_opt, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn assert_well_formed(xml: &str) {
+        for event_result in EventReader::new(xml.as_bytes()) {
+            event_result.unwrap();
+        }
+    }
+
+    #[test]
+    fn escapes_titles_for_xml() {
+        let graphml = drawing(GraphFormat::GraphMl, None);
+        assert_well_formed(&graphml);
+        assert!(graphml.contains("<data key=\"label\">Tom &amp; Jerry</data>"));
+        assert!(graphml.contains("<data key=\"label\">&quot;Weird Al&quot; Yankovic</data>"));
+        assert!(graphml.contains("<data key=\"label\">&lt;i&gt;Italic&lt;/i&gt; &amp; &apos;quoted&apos;</data>"));
+        assert!(graphml.contains("<edge source=\"n3\" target=\"n4\"/>"));
+
+        let gexf = drawing(GraphFormat::Gexf, None);
+        assert_well_formed(&gexf);
+        assert!(gexf.contains("<node id=\"1\" label=\"Tom &amp; Jerry\">"));
+        assert!(gexf.contains("<node id=\"2\" label=\"&quot;Weird Al&quot; Yankovic\">"));
+        assert!(gexf.contains("<attvalue for=\"crawled\" value=\"true\"/>"));
+        assert!(gexf.contains("<edge id=\"2\" source=\"3\" target=\"4\"/>"));
+    }
+
+    #[test]
+    fn escapes_quotes_for_dot_and_csv() {
+        assert_eq!(drawing(GraphFormat::Dot, None),
+                   "digraph wikiracer {\n  1 [label=\"Tom & Jerry\"];\n  2 [label=\"\\\"Weird Al\\\" Yankovic\"];\n  3 [label=\"Washington, D.C.\"];\n  4 [label=\"<i>Italic</i> & 'quoted'\"];\n  1 -> 2;\n  2 -> 3;\n  3 -> 4;\n}\n");
+        assert_eq!(drawing(GraphFormat::Csv, None),
+                   "source,target,source_label,target_label\n1,2,Tom & Jerry,\"\"\"Weird Al\"\" Yankovic\"\n2,3,\"\"\"Weird Al\"\" Yankovic\",\"Washington, D.C.\"\n3,4,\"Washington, D.C.\",<i>Italic</i> & 'quoted'\n");
+    }
+
+    #[test]
+    fn keeps_only_the_given_pages() {
+        let pages: HashSet<i64> = [2, 3].iter().cloned().collect();
+        assert_eq!(drawing(GraphFormat::Dot, Some(&pages)), "digraph wikiracer {\n  2 [label=\"\\\"Weird Al\\\" Yankovic\"];\n  3 [label=\"Washington, D.C.\"];\n  2 -> 3;\n}\n");
+    }
+
+    #[test]
+    fn finds_ego_pages_within_depth() {
+        // A -> B -> C -> D, E -> A and F -> B.
+        let mut store = MemoryStore::new();
+        for (index, title) in ["A", "B", "C", "D", "E", "F"].iter().enumerate() {
+            store.upsert_page(Some(title.to_string()), index as i64 + 1);
+        }
+        for &(to, from) in &[(2, 1), (3, 2), (4, 3), (1, 5), (2, 6)] {
+            store.add_link(to, from, None);
+        }
+
+        let ego = |depth: usize, direction: LinkDirection| -> Vec<String> {
+            let mut labels: Vec<String> = ego_pages(&store, "A", depth, direction).unwrap().into_iter().map(|page_id| store.page_label(page_id)).collect();
+            labels.sort();
+            labels
+        };
+
+        assert_eq!(ego(0, LinkDirection::Both), vec!["A"]);
+        assert_eq!(ego(1, LinkDirection::Links), vec!["A", "B"]);
+        assert_eq!(ego(2, LinkDirection::Links), vec!["A", "B", "C"]);
+        assert_eq!(ego(2, LinkDirection::Backlinks), vec!["A", "E"]);
+        assert_eq!(ego(1, LinkDirection::Both), vec!["A", "B", "E"]);
+        assert_eq!(ego(2, LinkDirection::Both), vec!["A", "B", "C", "E", "F"]);
+        assert!(ego_pages(&store, "Z", 1, LinkDirection::Both).is_none());
+    }
+}
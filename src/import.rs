@@ -0,0 +1,100 @@
+use querier_x_writer::QuerierToWriter;
+use std::io::BufRead;
+use std::mem;
+use std::sync::mpsc::SyncSender;
+use utils::WikiRacerError;
+
+const LINES_PER_PACKET: usize = 10000;
+
+// Reads the format written by `export_graph` and hands it to the writer stage
+// in packets, so an import goes through exactly the same inserts as a crawl.
+pub fn import_graph<R: BufRead>(input: R, sender: &SyncSender<QuerierToWriter>) -> Result<(), WikiRacerError> {
+    let mut pages = vec![];
+    let mut links = vec![];
+    let mut redirects = vec![];
+
+    for line_result in input.lines() {
+        let line = line_result.map_err(|_| WikiRacerError::FormatError("Import line could not be read"))?;
+        let mut fields = line.split('\t');
+
+        match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some("page"), Some(ext_page_id), Some(title), None) => {
+                let title_opt = if title.is_empty() { None } else { Some(title.to_string()) };
+                pages.push((title_opt, parse_ext_page_id(ext_page_id)?));
+            }
+            (Some("redirect"), Some(ext_page_id), Some(title), Some(target_ext_page_id)) => {
+                redirects.push((parse_ext_page_id(ext_page_id)?, title.to_string(), parse_ext_page_id(target_ext_page_id)?));
+            }
+            (Some("link"), Some(to_ext_page_id), Some(from_ext_page_id), position_str_opt) => {
+                let position_opt = match position_str_opt {
+                    Some(position_str) => Some(position_str.parse().map_err(|_| WikiRacerError::FormatError("Import link position was not a number"))?),
+                    None => None,
+                };
+                links.push((parse_ext_page_id(to_ext_page_id)?, parse_ext_page_id(from_ext_page_id)?, position_opt));
+            }
+            (Some(""), None, None, None) => (),
+            _ => return Err(WikiRacerError::FormatError("Import line was not a page, redirect or link")),
+        }
+
+        if pages.len() + links.len() + redirects.len() >= LINES_PER_PACKET {
+            sender.send(QuerierToWriter::Packet(mem::take(&mut pages), mem::take(&mut links), mem::take(&mut redirects))).unwrap();
+        }
+    }
+
+    sender.send(QuerierToWriter::Packet(pages, links, redirects)).unwrap();
+
+    Ok(())
+}
+
+fn parse_ext_page_id(ext_page_id: &str) -> Result<i64, WikiRacerError> {
+    ext_page_id.parse().map_err(|_| WikiRacerError::FormatError("Import page id was not a number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use export::export_graph;
+    use sqlite_store::SqliteStore;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::mpsc::sync_channel;
+    use store::GraphStore;
+    use utils::{open_database, open_or_create_database};
+    use writer::start_writer;
+
+    fn write_packets(store: SqliteStore, packets: Vec<QuerierToWriter>) -> SqliteStore {
+        let (sender, receiver) = sync_channel::<QuerierToWriter>(packets.len() + 1);
+        let writer = start_writer(store, receiver);
+        for packet in packets {
+            sender.send(packet).unwrap();
+        }
+        sender.send(QuerierToWriter::Exit).unwrap();
+        writer.join().unwrap()
+    }
+
+    #[test]
+    fn seeds_stay_uncrawled_across_export_and_import() {
+        let path = env::temp_dir().join(format!("wikiracer-import-{}-seeds.sqlite3", process::id()));
+        let _ = fs::remove_file(&path);
+        let path_str = path.to_str().unwrap();
+
+        // A was crawled and links to B, C was seeded but never crawled.
+        let store = write_packets(SqliteStore::new(open_or_create_database(path_str).unwrap()),
+                                  vec![QuerierToWriter::Seed(1), QuerierToWriter::Packet(vec![(Some("A".to_string()), 1), (None, 2)], vec![(2, 1, None)], vec![]), QuerierToWriter::Seed(3)]);
+        let stats = store.stats();
+        assert_eq!((stats.crawled_pages, stats.done, stats.queued), (1, 1, 2));
+
+        let mut exported = vec![];
+        export_graph(&open_database(path_str), &mut exported).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(String::from_utf8(exported.clone()).unwrap(), "page\t1\tA\npage\t2\t\npage\t3\t\nlink\t2\t1\n");
+
+        let (sender, receiver) = sync_channel::<QuerierToWriter>(2);
+        let writer = start_writer(SqliteStore::new(open_or_create_database(":memory:").unwrap()), receiver);
+        import_graph(&exported[..], &sender).unwrap();
+        sender.send(QuerierToWriter::Exit).unwrap();
+        let stats = writer.join().unwrap().stats();
+        assert_eq!((stats.pages, stats.crawled_pages, stats.done), (3, 1, 1));
+    }
+}
@@ -0,0 +1,354 @@
+use api_error::check_page;
+use response::{Alias, QueryResponse};
+use solver::{PathResult, PathStep};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use transport::{Transport, encode_query, get_json};
+use utils::WikiRacerError;
+
+pub const DEFAULT_MAX_DEPTH: usize = 6;
+
+const TITLES_PER_REQUEST: usize = 50;
+
+const FORWARD_PARAMS: &[(&str, &str)] = &[("prop", "links"), ("pllimit", "max"), ("plnamespace", "0"), ("redirects", "")];
+// Backlinks are asked for without following redirects, otherwise the API
+// answers with the backlinks of the redirect target instead of the redirect.
+const BACKWARD_PARAMS: &[(&str, &str)] = &[("prop", "linkshere"), ("lhprop", "title|redirect"), ("lhlimit", "max"), ("lhnamespace", "0")];
+
+pub fn live_search<T: Transport>(transport: &T, from: &str, to: &str, max_depth: usize) -> Result<PathResult, WikiRacerError> {
+    let from_title = match resolve_title(transport, from)? {
+        Some(title) => title,
+        None => return Ok(PathResult::Unknown(from.to_string())),
+    };
+    let to_title = match resolve_title(transport, to)? {
+        Some(title) => title,
+        None => return Ok(PathResult::Unknown(to.to_string())),
+    };
+
+    if from_title == to_title {
+        return Ok(PathResult::Found(vec![PathStep::new(from_title)]));
+    }
+
+    let mut forward = Side::new(from_title);
+    let mut backward = Side::new(to_title);
+
+    let mut depth = 0;
+
+    while depth < max_depth && !forward.frontier.is_empty() && !backward.frontier.is_empty() {
+        let meeting_opt = if forward.frontier.len() <= backward.frontier.len() {
+            let reached = expand_forward(transport, &mut forward)?;
+            reached.into_iter().find(|title| backward.parents.contains_key(title))
+        } else {
+            let reached = expand_backward(transport, &mut backward)?;
+            reached.into_iter().find(|title| forward.parents.contains_key(title))
+        };
+
+        depth += 1;
+
+        if let Some(meeting) = meeting_opt {
+            return Ok(PathResult::Found(join_path(&forward, &backward, meeting)));
+        }
+    }
+
+    Ok(PathResult::NoPath)
+}
+
+// What one direction of the search has reached so far.
+struct Side {
+    // Maps a title to its neighbour one step closer to this side's endpoint,
+    // the endpoint maps to itself.
+    parents: HashMap<String, String>,
+    // Maps a title to the redirect on its link to its parent. Forward that is
+    // the redirect followed to reach the title, backward the redirect the
+    // title links to.
+    redirects: HashMap<String, String>,
+    // Redirects found linking to a page of the backward side, by their title.
+    // A racer clicking one lands on its parent, so it takes the parent's place.
+    redirect_pages: HashSet<String>,
+    frontier: Vec<String>,
+}
+
+impl Side {
+    fn new(endpoint: String) -> Side {
+        let mut parents = HashMap::new();
+        parents.insert(endpoint.clone(), endpoint.clone());
+
+        Side {
+            parents,
+            redirects: HashMap::new(),
+            redirect_pages: HashSet::new(),
+            frontier: vec![endpoint],
+        }
+    }
+}
+
+fn join_path(forward: &Side, backward: &Side, meeting: String) -> Vec<PathStep> {
+    let step = |title: &String| {
+        PathStep {
+            title: title.clone(),
+            redirect_opt: forward.redirects.get(title).cloned(),
+        }
+    };
+
+    let mut path = vec![step(&meeting)];
+
+    let mut current = &meeting;
+    while forward.parents[current] != *current {
+        current = &forward.parents[current];
+        path.push(step(current));
+    }
+    path.reverse();
+
+    let mut current = &meeting;
+    if backward.redirect_pages.contains(current) {
+        path.pop();
+        current = &backward.parents[current];
+        path.push(PathStep {
+            title: current.clone(),
+            redirect_opt: Some(meeting.clone()),
+        });
+    }
+    while backward.parents[current] != *current {
+        let redirect_opt = backward.redirects.get(current).cloned();
+        current = &backward.parents[current];
+        path.push(PathStep {
+            title: current.clone(),
+            redirect_opt,
+        });
+    }
+
+    path
+}
+
+// Follows the links of the frontier, returning the newly reached titles, which
+// also make up the next frontier.
+fn expand_forward<T: Transport>(transport: &T, side: &mut Side) -> Result<Vec<String>, WikiRacerError> {
+    let mut reached = vec![];
+
+    let frontier = mem::take(&mut side.frontier);
+    query_titles(transport, &frontier, FORWARD_PARAMS, |response| {
+        add_aliases(&response.normalized, &mut side.parents);
+        // A target reached through a redirect has to be checked for a
+        // meeting like any other newly reached page.
+        for alias in add_aliases(&response.redirects, &mut side.parents) {
+            side.redirects.insert(alias.to.clone(), alias.from.clone());
+            reached.push(alias.to.clone());
+        }
+
+        for page in &response.pages {
+            if let Some(ref page_title) = page.title {
+                for link in &page.links {
+                    if let Some(ref link_title) = link.title {
+                        if !side.parents.contains_key(link_title) {
+                            side.parents.insert(link_title.clone(), page_title.clone());
+                            reached.push(link_title.clone());
+                        }
+                    }
+                }
+            }
+        }
+    })?;
+
+    side.frontier = reached.clone();
+    Ok(reached)
+}
+
+// Follows the backlinks of the frontier, returning the newly reached titles.
+// A redirect to a frontier page is as close as the page itself, so the pages
+// linking to it are reached on the same level, and it is left out of the next
+// frontier since its backlinks are already known.
+fn expand_backward<T: Transport>(transport: &T, side: &mut Side) -> Result<Vec<String>, WikiRacerError> {
+    let mut reached = vec![];
+    let mut redirects = vec![];
+
+    let frontier = mem::take(&mut side.frontier);
+    query_titles(transport, &frontier, BACKWARD_PARAMS, |response| {
+        add_aliases(&response.normalized, &mut side.parents);
+
+        for page in &response.pages {
+            if let Some(ref page_title) = page.title {
+                for link in &page.linkshere {
+                    if let Some(ref link_title) = link.title {
+                        if !side.parents.contains_key(link_title) {
+                            side.parents.insert(link_title.clone(), page_title.clone());
+                            reached.push(link_title.clone());
+                            if link.redirect {
+                                side.redirect_pages.insert(link_title.clone());
+                                redirects.push(link_title.clone());
+                            } else {
+                                side.frontier.push(link_title.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })?;
+
+    // Redirects to redirects are not followed on Wikipedia, so the backlinks
+    // of a redirect that are redirects themselves are skipped.
+    query_titles(transport, &redirects, BACKWARD_PARAMS, |response| {
+        for page in &response.pages {
+            if let Some(ref redirect_title) = page.title {
+                let target = match side.parents.get(redirect_title) {
+                    Some(target) => target.clone(),
+                    None => continue,
+                };
+                for link in &page.linkshere {
+                    if let Some(ref link_title) = link.title {
+                        if !link.redirect && !side.parents.contains_key(link_title) {
+                            side.parents.insert(link_title.clone(), target.clone());
+                            side.redirects.insert(link_title.clone(), redirect_title.clone());
+                            reached.push(link_title.clone());
+                            side.frontier.push(link_title.clone());
+                        }
+                    }
+                }
+            }
+        }
+    })?;
+
+    Ok(reached)
+}
+
+// Runs a query with `params` over `titles`, a chunk at a time, and hands every
+// response of every continuation to `handle`.
+fn query_titles<T: Transport, F: FnMut(&QueryResponse)>(transport: &T, titles: &[String], params: &[(&str, &str)], mut handle: F) -> Result<(), WikiRacerError> {
+    for chunk in titles.chunks(TITLES_PER_REQUEST) {
+        let joined_titles = chunk.join("|");
+        let mut continue_params: Vec<(String, String)> = vec![];
+
+        loop {
+            let mut query_params = vec![("action", "query"), ("format", "json"), ("titles", joined_titles.as_str())];
+            query_params.extend_from_slice(params);
+            query_params.extend(continue_params.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+
+            let response = QueryResponse::parse(&get_json(transport, &encode_query(&query_params))?)?;
+            handle(&response);
+
+            continue_params = response.continue_params;
+
+            if continue_params.is_empty() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A normalized or redirected title stands in for the title we asked about, so
+// it inherits that title's parent. Returns the aliases that reached a new title.
+fn add_aliases<'a>(aliases: &'a [Alias], parents: &mut HashMap<String, String>) -> Vec<&'a Alias> {
+    let mut added = vec![];
+    for alias in aliases {
+        if let Some(parent) = parents.get(&alias.from).cloned() {
+            if !parents.contains_key(&alias.to) {
+                parents.insert(alias.to.clone(), parent);
+                added.push(alias);
+            }
+        }
+    }
+    added
+}
+
+fn resolve_title<T: Transport>(transport: &T, title_or_ext_page_id: &str) -> Result<Option<String>, WikiRacerError> {
+    let key = if title_or_ext_page_id.parse::<u64>().is_ok() { "pageids" } else { "titles" };
+
+    let response = QueryResponse::parse(&get_json(transport, &encode_query(&[("action", "query"), ("format", "json"), ("redirects", ""), (key, title_or_ext_page_id)]))?)?;
+
+    match response.pages.first() {
+        Some(page) if check_page(page).is_ok() => Ok(page.title.clone()),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::FixtureTransport;
+
+    // Builds a fixture wiki where `links` holds the links of each page and
+    // `backlinks` the backlinks, with redirects marked, along with the lookup
+    // of each endpoint.
+    fn wiki(endpoints: &[&str], links: &[(&str, &[&str])], backlinks: &[(&str, &[(&str, bool)])]) -> FixtureTransport {
+        let mut fixtures = FixtureTransport::new();
+
+        for (index, title) in endpoints.iter().enumerate() {
+            fixtures.insert(&format!("action=query&format=json&redirects=&titles={}", title), format!(r#"{{"query":{{"pages":{{"{0}":{{"pageid":{0},"ns":0,"title":"{1}"}}}}}}}}"#, index + 1, title));
+        }
+        for &(title, linked) in links {
+            let entries = linked.iter().map(|link| format!(r#"{{"ns":0,"title":"{}"}}"#, link)).collect::<Vec<_>>();
+            fixtures.insert(&format!("action=query&format=json&titles={}&prop=links&pllimit=max&plnamespace=0&redirects=", title), page(title, "links", &entries));
+        }
+        for &(title, linked) in backlinks {
+            let entries = linked.iter().map(|&(link, redirect)| format!(r#"{{"ns":0,"title":"{}"{}}}"#, link, if redirect { r#","redirect":"""# } else { "" })).collect::<Vec<_>>();
+            fixtures.insert(&format!("action=query&format=json&titles={}&prop=linkshere&lhprop=title|redirect&lhlimit=max&lhnamespace=0", title), page(title, "linkshere", &entries));
+        }
+
+        fixtures
+    }
+
+    fn page(title: &str, prop: &str, entries: &[String]) -> String {
+        format!(r#"{{"batchcomplete":"","query":{{"pages":{{"-1":{{"ns":0,"title":"{}","{}":[{}]}}}}}}}}"#, title, prop, entries.join(","))
+    }
+
+    fn path(steps: &[(&str, Option<&str>)]) -> PathResult {
+        PathResult::Found(steps.iter()
+                               .map(|&(title, redirect_opt)| {
+                                   PathStep {
+                                       title: title.to_string(),
+                                       redirect_opt: redirect_opt.map(|redirect| redirect.to_string()),
+                                   }
+                               })
+                               .collect())
+    }
+
+    #[test]
+    fn follows_links_across_continuations() {
+        let mut fixtures = wiki(&["A", "T"], &[], &[]);
+        fixtures.insert("action=query&format=json&titles=A&prop=links&pllimit=max&plnamespace=0&redirects=",
+                        r#"{"continue":{"plcontinue":"1|0|C","continue":"||"},"query":{"pages":{"1":{"pageid":1,"ns":0,"title":"A","links":[{"ns":0,"title":"B"}]}}}}"#.to_string());
+        fixtures.insert("action=query&format=json&titles=A&prop=links&pllimit=max&plnamespace=0&redirects=&plcontinue=1|0|C&continue=||",
+                        r#"{"batchcomplete":"","query":{"pages":{"1":{"pageid":1,"ns":0,"title":"A","links":[{"ns":0,"title":"C"},{"ns":0,"title":"T"}]}}}}"#.to_string());
+
+        assert_eq!(live_search(&fixtures, "A", "T", DEFAULT_MAX_DEPTH).unwrap(), path(&[("A", None), ("T", None)]));
+    }
+
+    #[test]
+    fn follows_redirects_forward() {
+        let mut fixtures = wiki(&["A", "T"], &[("A", &["B"]), ("B", &["R"])], &[]);
+        fixtures.insert("action=query&format=json&titles=R&prop=links&pllimit=max&plnamespace=0&redirects=",
+                        r#"{"batchcomplete":"","query":{"redirects":[{"from":"R","to":"T"}],"pages":{"2":{"pageid":2,"ns":0,"title":"T","links":[{"ns":0,"title":"A"}]}}}}"#.to_string());
+
+        assert_eq!(live_search(&fixtures, "A", "T", DEFAULT_MAX_DEPTH).unwrap(), path(&[("A", None), ("B", None), ("T", Some("R"))]));
+    }
+
+    #[test]
+    fn follows_backlinks() {
+        let fixtures = wiki(&["A", "T"], &[("A", &["B", "C", "D"])], &[("T", &[("X", false)]), ("X", &[("B", false)])]);
+
+        assert_eq!(live_search(&fixtures, "A", "T", DEFAULT_MAX_DEPTH).unwrap(), path(&[("A", None), ("B", None), ("X", None), ("T", None)]));
+    }
+
+    #[test]
+    fn follows_backlinks_of_redirects_on_the_same_level() {
+        let fixtures = wiki(&["A", "T"], &[("A", &["B", "C"])], &[("T", &[("R", true), ("D", false)]), ("R", &[("B", false), ("S", true)])]);
+
+        assert_eq!(live_search(&fixtures, "A", "T", DEFAULT_MAX_DEPTH).unwrap(), path(&[("A", None), ("B", None), ("T", Some("R"))]));
+    }
+
+    #[test]
+    fn meets_at_a_redirect_found_backward() {
+        let fixtures = wiki(&["A", "T"], &[("A", &["R", "C"])], &[("T", &[("R", true)]), ("R", &[])]);
+
+        assert_eq!(live_search(&fixtures, "A", "T", DEFAULT_MAX_DEPTH).unwrap(), path(&[("A", None), ("T", Some("R"))]));
+    }
+
+    #[test]
+    fn stops_at_the_maximum_depth() {
+        let fixtures = wiki(&["A", "T"], &[("A", &["B", "C"])], &[("T", &[("X", false)])]);
+
+        assert_eq!(live_search(&fixtures, "A", "T", 2).unwrap(), PathResult::NoPath);
+    }
+}
@@ -1,75 +1,543 @@
+extern crate bzip2;
+extern crate ctrlc;
+extern crate flate2;
 extern crate hyper;
 extern crate hyper_native_tls;
 extern crate rusqlite;
 extern crate json;
+extern crate memmap;
 extern crate time;
+extern crate url;
+extern crate xml;
+#[macro_use]
+extern crate clap;
 
-use std::io::{self, Read};
-use std::sync::mpsc::channel;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use rusqlite::Connection;
+use std::cmp;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::{channel, sync_channel};
+use std::thread;
 
 mod main_x_page_id;
 mod page_id_x_querier;
 mod querier_x_writer;
 
+mod api_error;
+mod console;
+mod live_search;
 mod page_id;
 mod querier;
+mod response;
+mod solver;
+mod throttle;
+mod transport;
 mod writer;
 
+mod checkpoint;
+mod csr;
+mod frontier;
+mod memory_store;
+mod sqlite_store;
+mod store;
+
+mod export;
+mod graph_export;
+mod import;
+mod sql_dump;
+mod sql_import;
+mod wikitext;
+mod xml_dump;
+mod xml_import;
+mod schema;
+mod stats;
+
+mod bench;
+mod fixture_server;
+
 mod utils;
 
+use bench::bench_writer;
+use checkpoint::{last_checkpoint, record_checkpoint};
+use console::run_console;
+use csr::{CsrGraph, export_csr};
+use export::export_graph;
+use frontier::IN_FLIGHT;
+use graph_export::{GraphFormat, ego_pages, export_drawing};
+use fixture_server::start_fixture_server;
+use import::import_graph;
+use live_search::{DEFAULT_MAX_DEPTH, live_search};
 use main_x_page_id::MainToPageId;
+use memory_store::MemoryStore;
 use page_id::start_pageid;
 use page_id_x_querier::PageIdToQuerier;
-use querier::start_querier;
+use querier::{LinkDirection, start_querier};
 use querier_x_writer::QuerierToWriter;
+use solver::{PathResult, find_path};
+use sql_import::import_sql_dumps;
+use sqlite_store::SqliteStore;
+use stats::{count_state, get_stats, print_stats};
+use store::GraphStore;
+use throttle::{DEFAULT_MAXLAG, DEFAULT_MAX_RETRIES, DEFAULT_REQUESTS_PER_SECOND, RateLimiter, ThrottledTransport};
+use transport::{FixtureTransport, HttpTransport, RecordingTransport, Transport, WIKIPEDIA_API_URL};
 
-use utils::create_database;
+use utils::{DEFAULT_DATABASE_PATH, WikiRacerError, open_database, open_or_create_database};
 use writer::start_writer;
+use xml_import::import_xml_dump;
+
+// Packets an import may read ahead of what the writer has stored.
+const IMPORT_PACKET_BUFFER: usize = 4;
 
 fn main() {
-    let start_time = time::now();
+    let default_max_depth = DEFAULT_MAX_DEPTH.to_string();
+    let default_rate = DEFAULT_REQUESTS_PER_SECOND.to_string();
+    let default_maxlag = DEFAULT_MAXLAG.to_string();
+    let default_retries = DEFAULT_MAX_RETRIES.to_string();
 
-    let (page_id_sender, querier_receiver) = channel::<PageIdToQuerier>();
-    let (querier_sender, writer_receiver) = channel::<QuerierToWriter>();
-    let (main_sender, page_id_receiver) = channel::<MainToPageId>();
+    let matches = App::new("wikiracer")
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::with_name("database")
+            .long("database")
+            .short("d")
+            .takes_value(true)
+            .default_value(DEFAULT_DATABASE_PATH)
+            .help("Path of the sqlite database"))
+        .arg(Arg::with_name("api-url")
+            .long("api-url")
+            .takes_value(true)
+            .default_value(WIKIPEDIA_API_URL)
+            .help("api.php endpoint to query, http or https"))
+        .arg(Arg::with_name("fixtures")
+            .long("fixtures")
+            .takes_value(true)
+            .conflicts_with("record")
+            .help("Directory of fixtures to answer queries from instead of the network"))
+        .arg(Arg::with_name("rate")
+            .long("rate")
+            .takes_value(true)
+            .default_value(&default_rate)
+            .help("Requests per second to send at most, 0 for no limit"))
+        .arg(Arg::with_name("maxlag")
+            .long("maxlag")
+            .takes_value(true)
+            .default_value(&default_maxlag)
+            .help("Seconds of replication lag at which the API should refuse us, 0 to not send maxlag"))
+        .arg(Arg::with_name("retries")
+            .long("retries")
+            .takes_value(true)
+            .default_value(&default_retries)
+            .help("Times a failed request is retried with backoff before giving up"))
+        .arg(Arg::with_name("record")
+            .long("record")
+            .takes_value(true)
+            .help("Directory to save every response to as a fixture"))
+        .subcommand(SubCommand::with_name("crawl")
+            .about("Crawls links from the API into the database until \"exit\" is read from stdin")
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .short("s")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("External page id or title of a page to start crawling from, anything but a number is taken as a title"))
+            .arg(Arg::with_name("seed-title")
+                .long("seed-title")
+                .short("t")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Title of a page to start crawling from, for titles that are numbers, redirects are followed"))
+            .arg(Arg::with_name("direction")
+                .long("direction")
+                .takes_value(true)
+                .possible_values(&["backlinks", "links", "both"])
+                .default_value("backlinks")
+                .help("Which links of each crawled page to store"))
+            .arg(Arg::with_name("page-id-buffer")
+                .long("page-id-buffer")
+                .takes_value(true)
+                .default_value("200")
+                .help("Pages handed to the queriers that have not been written yet"))
+            .arg(Arg::with_name("queriers")
+                .long("queriers")
+                .takes_value(true)
+                .default_value("4")
+                .help("Querier threads making requests at once, all under the one rate limit"))
+            .arg(Arg::with_name("querier-buffer")
+                .long("querier-buffer")
+                .takes_value(true)
+                .default_value("10")
+                .help("Packets the queriers may get ahead of the writer")))
+        .subcommand(SubCommand::with_name("path")
+            .about("Finds the shortest chain of links between two pages")
+            .arg(Arg::with_name("from")
+                .required(true)
+                .help("Title or external page id to start from"))
+            .arg(Arg::with_name("to")
+                .required(true)
+                .help("Title or external page id to reach"))
+            .arg(Arg::with_name("live")
+                .long("live")
+                .help("Searches the API directly instead of the database"))
+            .arg(Arg::with_name("graph")
+                .long("graph")
+                .takes_value(true)
+                .conflicts_with("live")
+                .help("Searches a file written by export instead of the database, mapped into memory if compiled with --format csr, read into it otherwise"))
+            .arg(Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true)
+                .default_value(&default_max_depth)
+                .help("Levels a live search expands before giving up")))
+        .subcommand(SubCommand::with_name("stats").about("Prints page and link counts of the database"))
+        .subcommand(SubCommand::with_name("export")
+            .about("Writes the pages and links of the database to a file")
+            .arg(Arg::with_name("file")
+                .required(true)
+                .help("File to write, \"-\" for stdout"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["tsv", "csr", "dot", "graphml", "gexf", "csv"])
+                .default_value("tsv")
+                .help("tsv to read back with import, csr for a compiled graph path --graph searches without loading, dot, graphml, gexf or a csv edge list for other tools"))
+            .arg(Arg::with_name("ego")
+                .long("ego")
+                .takes_value(true)
+                .help("Title or external page id of a crawled page, to only write the pages near it, for dot, graphml, gexf and csv"))
+            .arg(Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .default_value("1")
+                .help("Links away from the --ego page a page may be"))
+            .arg(Arg::with_name("ego-direction")
+                .long("ego-direction")
+                .takes_value(true)
+                .possible_values(&["backlinks", "links", "both"])
+                .default_value("links")
+                .help("Which links to follow away from the --ego page")))
+        .subcommand(SubCommand::with_name("import")
+            .about("Reads pages and links written by export into the database")
+            .arg(Arg::with_name("file")
+                .required(true)
+                .help("File to read, \"-\" for stdin")))
+        .subcommand(SubCommand::with_name("import-sql")
+            .about("Reads pages, redirects and links from the page, redirect and pagelinks tables of a Wikipedia SQL dump")
+            .arg(Arg::with_name("dir")
+                .required(true)
+                .help("Directory holding page.sql.gz, redirect.sql.gz and pagelinks.sql.gz, and linktarget.sql.gz for newer dumps")))
+        .subcommand(SubCommand::with_name("import-xml")
+            .about("Reads articles, redirects and the links in their wikitext from a pages-articles XML dump")
+            .arg(Arg::with_name("file")
+                .required(true)
+                .help("pages-articles.xml or pages-articles.xml.bz2, read twice so it cannot be stdin")))
+        .subcommand(SubCommand::with_name("serve-fixtures")
+            .about("Serves a directory of fixtures as a local api.php over plain HTTP")
+            .arg(Arg::with_name("dir")
+                .required(true)
+                .help("Directory of fixtures to serve"))
+            .arg(Arg::with_name("port")
+                .long("port")
+                .takes_value(true)
+                .default_value("8080")
+                .help("Port to listen on")))
+        .subcommand(SubCommand::with_name("bench-writer")
+            .about("Times the writer stage on a made up crawl in a scratch database")
+            .arg(Arg::with_name("pages")
+                .long("pages")
+                .takes_value(true)
+                .default_value("10000")
+                .help("Pages to crawl"))
+            .arg(Arg::with_name("links-per-page")
+                .long("links-per-page")
+                .takes_value(true)
+                .default_value("100")
+                .help("Links stored for every crawled page")))
+        .get_matches();
 
-    let conn = create_database();
-    conn.close().unwrap();
+    let database_path = matches.value_of("database").unwrap().to_string();
+
+    match matches.subcommand() {
+        ("crawl", Some(sub_matches)) => {
+            let rate_limiter = create_rate_limiter(&matches);
+            // Each querier gets a client of its own, only the rate limit is shared.
+            let queriers = value_t!(sub_matches, "queriers", usize).unwrap_or_else(|e| e.exit());
+            let transports = (0..cmp::max(queriers, 1)).map(|_| create_transport(&matches, &rate_limiter)).collect();
+            crawl(database_path, transports, rate_limiter, sub_matches)
+        }
+        ("path", Some(sub_matches)) => path(database_path, create_transport(&matches, &create_rate_limiter(&matches)), sub_matches),
+        ("stats", Some(_)) => print_stats(&get_stats(&open_or_exit(&database_path))),
+        ("export", Some(sub_matches)) => export(database_path, sub_matches),
+        ("import", Some(sub_matches)) => import(database_path, sub_matches),
+        ("import-sql", Some(sub_matches)) => import_sql(database_path, sub_matches),
+        ("import-xml", Some(sub_matches)) => import_xml(database_path, sub_matches),
+        ("serve-fixtures", Some(sub_matches)) => serve_fixtures(sub_matches),
+        ("bench-writer", Some(sub_matches)) => {
+            let pages = value_t!(sub_matches, "pages", usize).unwrap_or_else(|e| e.exit());
+            let links_per_page = value_t!(sub_matches, "links-per-page", usize).unwrap_or_else(|e| e.exit());
+            bench_writer(cmp::max(pages, 1), links_per_page);
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn create_rate_limiter(matches: &ArgMatches) -> RateLimiter {
+    let rate = value_t!(matches, "rate", f64).unwrap_or_else(|e| e.exit());
+    RateLimiter::new(rate)
+}
 
-    main_sender.send(MainToPageId::Packet("1095706".to_string())).unwrap();
+fn create_transport(matches: &ArgMatches, rate_limiter: &RateLimiter) -> Box<dyn Transport + Send> {
+    if let Some(fixtures_dir) = matches.value_of("fixtures") {
+        return Box::new(FixtureTransport::load(Path::new(fixtures_dir)).unwrap());
+    }
 
-    let pageid = start_pageid(10, page_id_sender, page_id_receiver);
-    let querier = start_querier(10, querier_receiver, querier_sender);
-    let writer = start_writer(writer_receiver);
+    let maxlag = value_t!(matches, "maxlag", u64).unwrap_or_else(|e| e.exit());
+    let retries = value_t!(matches, "retries", u32).unwrap_or_else(|e| e.exit());
 
-    let mut exit = false;
+    let http_transport = HttpTransport::new(matches.value_of("api-url").unwrap());
+    let throttled_transport = ThrottledTransport::new(http_transport, rate_limiter.clone(), maxlag, retries);
 
-    let mut buffer = String::new();
-    let stdin = io::stdin();
+    // Recording outside of the throttle keeps maxlag out of the saved queries.
+    match matches.value_of("record") {
+        Some(record_dir) => Box::new(RecordingTransport::new(throttled_transport, Path::new(record_dir)).unwrap()),
+        None => Box::new(throttled_transport),
+    }
+}
 
-    while !exit {
-        {
-            let mut handle = stdin.lock();
-            handle.read_to_string(&mut buffer).unwrap();
+// A database written by a newer build may hold what this one cannot read, so
+// it is left alone rather than opened.
+fn open_or_exit(database_path: &str) -> Connection {
+    match open_or_create_database(database_path) {
+        Ok(conn) => conn,
+        Err(WikiRacerError::SchemaTooNew(version, known_version)) => {
+            println!("{} has schema version {}, newer than the {} this build knows, upgrade wikiracer to open it", database_path, version, known_version);
+            process::exit(1);
         }
-        match buffer.as_str() {
-            "exit" => {
-                println!("Exiting");
-                exit = true;
-            }
-            _ => (),
+        Err(err) => {
+            println!("Opening {} failed: {}", database_path, err);
+            process::exit(1);
         }
     }
+}
 
-    main_sender.send(MainToPageId::Exit).unwrap();
+fn crawl(database_path: String, transports: Vec<Box<dyn Transport + Send>>, rate_limiter: RateLimiter, matches: &ArgMatches) {
+    let page_id_buffer_size = value_t!(matches, "page-id-buffer", usize).unwrap_or_else(|e| e.exit());
+    let querier_buffer_size = value_t!(matches, "querier-buffer", usize).unwrap_or_else(|e| e.exit());
+    let direction = matches.value_of("direction").unwrap().parse::<LinkDirection>().unwrap();
+
+    let start_time = time::now();
+
+    let (page_id_sender, querier_receiver) = sync_channel::<PageIdToQuerier>(page_id_buffer_size);
+    let (querier_sender, writer_receiver) = sync_channel::<QuerierToWriter>(querier_buffer_size);
+    // Seeds are sent before the stages start, so this one is not bounded.
+    let (main_sender, page_id_receiver) = channel::<MainToPageId>();
+
+    let conn = open_or_exit(&database_path);
+    if let Some(checkpoint) = last_checkpoint(&conn) {
+        println!("Last checkpoint {}: {} queued, {} done, {} failed", checkpoint.time, checkpoint.queued, checkpoint.done, checkpoint.failed);
+    }
+    let in_flight = count_state(&conn, IN_FLIGHT);
+    if in_flight > 0 {
+        println!("Last crawl stopped without a checkpoint, {} pages in flight are queued again", in_flight);
+    }
+    conn.close().unwrap();
+
+    if let Some(seeds) = matches.values_of("seed") {
+        for seed in seeds {
+            let packet = match seed.parse() {
+                Ok(ext_page_id) => MainToPageId::Packet(ext_page_id),
+                Err(_) => MainToPageId::SeedTitle(seed.to_string()),
+            };
+            main_sender.send(packet).unwrap();
+        }
+    }
+
+    if let Some(seed_titles) = matches.values_of("seed-title") {
+        for seed_title in seed_titles {
+            main_sender.send(MainToPageId::SeedTitle(seed_title.to_string())).unwrap();
+        }
+    }
+
+    let pageid = start_pageid(SqliteStore::new(open_database(&database_path)), page_id_buffer_size, page_id_sender, page_id_receiver);
+    let querier = start_querier(transports, direction, querier_receiver, querier_sender);
+    let writer = start_writer(SqliteStore::new(open_database(&database_path)), writer_receiver);
+
+    // The first signal stops the crawl the same way `exit` does, draining
+    // every stage, a second one gives up on that and stops at once.
+    let signal_sender = main_sender.clone();
+    let mut signalled = false;
+    ctrlc::set_handler(move || {
+            if signalled {
+                println!("Stopping without a checkpoint");
+                process::exit(130);
+            }
+            signalled = true;
+            println!("Exiting, finishing the pages in flight");
+            let _ = signal_sender.send(MainToPageId::Exit);
+        })
+        .unwrap();
+
+    // Reading the console would block, so it gets a thread of its own that
+    // is simply left behind once the stages are done.
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        run_console(stdin.lock(), &main_sender, &rate_limiter);
+    });
 
     pageid.join().unwrap();
     querier.join().unwrap();
     writer.join().unwrap();
 
+    let checkpoint = record_checkpoint(&open_database(&database_path));
+    println!("Checkpoint {}: {} queued, {} done, {} failed", checkpoint.time, checkpoint.queued, checkpoint.done, checkpoint.failed);
+
     let end_time = time::now();
 
     println!("Start Time: {}", start_time.ctime());
     println!("End Time: {}", end_time.ctime());
     println!("Run Time: {}", end_time - start_time);
 }
+
+fn path(database_path: String, transport: Box<dyn Transport + Send>, matches: &ArgMatches) {
+    let from = matches.value_of("from").unwrap();
+    let to = matches.value_of("to").unwrap();
+
+    let path_result = if matches.is_present("live") {
+        let max_depth = value_t!(matches, "max-depth", usize).unwrap_or_else(|e| e.exit());
+        live_search(&transport, from, to, max_depth)
+    } else if let Some(graph_path) = matches.value_of("graph") {
+        if CsrGraph::is_compiled(Path::new(graph_path)) {
+            match CsrGraph::open(Path::new(graph_path)) {
+                Ok(graph) => Ok(find_path(&graph, from, to)),
+                Err(err) => {
+                    println!("Opening {} failed: {}", graph_path, err);
+                    process::exit(1);
+                }
+            }
+        } else {
+            let (store, read_result) = read_graph(MemoryStore::new(), graph_path);
+            if let Err(err) = read_result {
+                println!("Reading {} stopped early: {}", graph_path, err);
+            }
+            Ok(find_path(&store, from, to))
+        }
+    } else {
+        Ok(find_path(&SqliteStore::new(open_or_exit(&database_path)), from, to))
+    };
+
+    match path_result {
+        Ok(PathResult::Found(steps)) => println!("{}", steps.iter().map(|step| step.to_string()).collect::<Vec<String>>().join(" -> ")),
+        Ok(PathResult::NoPath) => println!("No path from {:?} to {:?}", from, to),
+        Ok(PathResult::Unknown(page)) => println!("Unknown page {:?}", page),
+        Err(err) => println!("Live search failed: {}", err),
+    }
+}
+
+fn export(database_path: String, matches: &ArgMatches) {
+    let conn = open_or_exit(&database_path);
+    let format = matches.value_of("format").unwrap();
+    let graph_format_opt = format.parse::<GraphFormat>().ok();
+
+    let pages_opt = match matches.value_of("ego") {
+        Some(_) if graph_format_opt.is_none() => {
+            println!("--ego only applies to dot, graphml, gexf and csv, not {}", format);
+            process::exit(1);
+        }
+        Some(center) => {
+            let depth = value_t!(matches, "depth", usize).unwrap_or_else(|e| e.exit());
+            let direction = matches.value_of("ego-direction").unwrap().parse::<LinkDirection>().unwrap();
+            match ego_pages(&SqliteStore::new(open_or_exit(&database_path)), center, depth, direction) {
+                Some(pages) => Some(pages),
+                None => {
+                    println!("Unknown page {:?}", center);
+                    process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut out: Box<dyn Write> = match matches.value_of("file").unwrap() {
+        "-" => Box::new(BufWriter::new(io::stdout())),
+        file_path => Box::new(BufWriter::new(File::create(file_path).unwrap())),
+    };
+
+    match (format, graph_format_opt) {
+        (_, Some(graph_format)) => export_drawing(&conn, graph_format, pages_opt.as_ref(), &mut out).unwrap(),
+        ("csr", None) => export_csr(&conn, &mut out).unwrap(),
+        _ => export_graph(&conn, &mut out).unwrap(),
+    }
+}
+
+fn import(database_path: String, matches: &ArgMatches) {
+    let (_, import_result) = read_graph(SqliteStore::new(open_or_exit(&database_path)), matches.value_of("file").unwrap());
+
+    if let Err(err) = import_result {
+        println!("Import stopped early: {}", err);
+    }
+}
+
+// Reads a file written by export into the store through a writer stage, and
+// hands the store back.
+fn read_graph<S: GraphStore + Send + 'static>(store: S, file_path: &str) -> (S, Result<(), WikiRacerError>) {
+    let (import_sender, writer_receiver) = sync_channel::<QuerierToWriter>(IMPORT_PACKET_BUFFER);
+    let writer = start_writer(store, writer_receiver);
+
+    let import_result = match file_path {
+        "-" => {
+            let stdin = io::stdin();
+            import_graph(stdin.lock(), &import_sender)
+        }
+        file_path => import_graph(BufReader::new(File::open(file_path).unwrap()), &import_sender),
+    };
+
+    import_sender.send(QuerierToWriter::Exit).unwrap();
+    (writer.join().unwrap(), import_result)
+}
+
+fn import_sql(database_path: String, matches: &ArgMatches) {
+    let mut conn = open_or_exit(&database_path);
+
+    let start_time = time::now();
+
+    match import_sql_dumps(&mut conn, Path::new(matches.value_of("dir").unwrap())) {
+        Ok(stats) => println!("Imported {} pages, {} redirects and {} links", stats.pages, stats.redirects, stats.links),
+        Err(err) => println!("SQL import failed: {}", err),
+    }
+
+    println!("Run Time: {}", time::now() - start_time);
+}
+
+fn import_xml(database_path: String, matches: &ArgMatches) {
+    let start_time = time::now();
+
+    let (import_sender, writer_receiver) = sync_channel::<QuerierToWriter>(IMPORT_PACKET_BUFFER);
+    let writer = start_writer(SqliteStore::new(open_or_exit(&database_path)), writer_receiver);
+
+    let import_result = import_xml_dump(Path::new(matches.value_of("file").unwrap()), &import_sender);
+
+    import_sender.send(QuerierToWriter::Exit).unwrap();
+    writer.join().unwrap();
+
+    match import_result {
+        Ok(stats) => println!("Imported {} pages, {} redirects and {} links", stats.pages, stats.redirects, stats.links),
+        Err(err) => println!("XML import stopped early: {}", err),
+    }
+
+    println!("Run Time: {}", time::now() - start_time);
+}
+
+fn serve_fixtures(matches: &ArgMatches) {
+    let port = value_t!(matches, "port", u16).unwrap_or_else(|e| e.exit());
+    let fixtures = FixtureTransport::load(Path::new(matches.value_of("dir").unwrap())).unwrap();
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    println!("Serving {} fixtures on http://{}/w/api.php", fixtures.len(), listener.local_addr().unwrap());
+
+    start_fixture_server(listener, fixtures).join().unwrap();
+}
@@ -1,5 +1,12 @@
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum MainToPageId {
-    Packet(String),
+    // A seed by ext page id.
+    Packet(i64),
+    // A seed by title, resolved by the querier.
+    SeedTitle(String),
+    Pause,
+    Resume,
+    Status,
+    Flush,
     Exit,
 }
@@ -0,0 +1,231 @@
+use frontier::{DONE, FAILED, IN_FLIGHT, MAX_ATTEMPTS, QUEUED};
+use stats::Stats;
+use std::collections::{HashMap, HashSet};
+use store::{GraphStore, GraphView};
+
+// The graph held in memory, for solving races on a graph that is only
+// needed for as long as the process runs. Link positions are not kept.
+pub struct MemoryStore {
+    // Page ids count up from 1 like the database's, page `n` is at `n - 1`.
+    pages: Vec<MemoryPage>,
+    page_ids: HashMap<i64, i64>,
+    links: HashMap<i64, Vec<(i64, Option<i64>)>>,
+    backlinks: HashMap<i64, Vec<(i64, Option<i64>)>>,
+    link_ends: HashSet<(i64, i64)>,
+    // (target page id, title) by the page id of the redirect.
+    redirects: HashMap<i64, (i64, String)>,
+}
+
+struct MemoryPage {
+    title_opt: Option<String>,
+    ext_page_id: i64,
+    // (state, depth, attempts) once the page is on the frontier.
+    frontier_opt: Option<(&'static str, i64, i64)>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            pages: vec![],
+            page_ids: HashMap::new(),
+            links: HashMap::new(),
+            backlinks: HashMap::new(),
+            link_ends: HashSet::new(),
+            redirects: HashMap::new(),
+        }
+    }
+
+    fn add_page(&mut self, ext_page_id: i64) -> i64 {
+        if let Some(&page_id) = self.page_ids.get(&ext_page_id) {
+            return page_id;
+        }
+
+        self.pages.push(MemoryPage {
+            title_opt: None,
+            ext_page_id,
+            frontier_opt: None,
+        });
+        let page_id = self.pages.len() as i64;
+        self.page_ids.insert(ext_page_id, page_id);
+        page_id
+    }
+
+    fn page(&self, page_id: i64) -> &MemoryPage {
+        &self.pages[page_id as usize - 1]
+    }
+
+    fn page_mut(&mut self, page_id: i64) -> &mut MemoryPage {
+        &mut self.pages[page_id as usize - 1]
+    }
+
+    fn set_state(&mut self, ext_page_id: i64, state: &'static str) {
+        if let Some(&page_id) = self.page_ids.get(&ext_page_id) {
+            if let Some(ref mut frontier) = self.page_mut(page_id).frontier_opt {
+                frontier.0 = state;
+            }
+        }
+    }
+
+    fn is_crawled(&self, page_id: i64) -> bool {
+        self.page(page_id).frontier_opt.is_some_and(|frontier| frontier.0 == DONE)
+    }
+
+    fn count_state(&self, state: &str) -> i64 {
+        self.pages.iter().filter(|page| page.frontier_opt.is_some_and(|frontier| frontier.0 == state)).count() as i64
+    }
+}
+
+impl GraphView for MemoryStore {
+    fn find_crawled_page(&self, title_or_ext_page_id: &str) -> Option<i64> {
+        let ext_page_id_opt: Option<i64> = title_or_ext_page_id.parse().ok();
+        let is_named = |title_opt: Option<&String>, ext_page_id: i64| title_opt.is_some_and(|title| title == title_or_ext_page_id) || Some(ext_page_id) == ext_page_id_opt;
+
+        self.redirects
+            .iter()
+            .find(|&(&page_id, &(target_page_id, ref title))| self.is_crawled(target_page_id) && is_named(Some(title), self.page(page_id).ext_page_id))
+            .map(|(_, &(target_page_id, _))| target_page_id)
+            .or_else(|| (1..self.pages.len() as i64 + 1).find(|&page_id| self.is_crawled(page_id) && is_named(self.page(page_id).title_opt.as_ref(), self.page(page_id).ext_page_id)))
+    }
+
+    fn links_from(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        self.links.get(&page_id).cloned().unwrap_or_default()
+    }
+
+    fn links_to(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        self.backlinks.get(&page_id).cloned().unwrap_or_default()
+    }
+
+    fn page_label(&self, page_id: i64) -> String {
+        let page = self.page(page_id);
+        page.title_opt.clone().unwrap_or_else(|| page.ext_page_id.to_string())
+    }
+
+    fn redirect_title(&self, redirect_page_id: i64) -> String {
+        match self.redirects.get(&redirect_page_id) {
+            Some((_, title)) => title.clone(),
+            None => self.page_label(redirect_page_id),
+        }
+    }
+}
+
+impl GraphStore for MemoryStore {
+    fn upsert_page(&mut self, title_opt: Option<String>, ext_page_id: i64) {
+        let page_id = self.add_page(ext_page_id);
+
+        if let Some(title) = title_opt {
+            let page = self.page_mut(page_id);
+            page.title_opt = Some(title);
+            match page.frontier_opt {
+                Some(ref mut frontier) => frontier.0 = DONE,
+                None => page.frontier_opt = Some((DONE, 0, 0)),
+            }
+        }
+    }
+
+    fn add_link(&mut self, to_ext_page_id: i64, from_ext_page_id: i64, _position_opt: Option<i64>) {
+        if let (Some(&to_page_id), Some(&from_page_id)) = (self.page_ids.get(&to_ext_page_id), self.page_ids.get(&from_ext_page_id)) {
+            let (to_page_id, redirect_page_id_opt) = match self.redirects.get(&to_page_id) {
+                Some(&(target_page_id, _)) => (target_page_id, Some(to_page_id)),
+                None => (to_page_id, None),
+            };
+            if to_page_id != from_page_id && self.link_ends.insert((to_page_id, from_page_id)) {
+                self.links.entry(from_page_id).or_default().push((to_page_id, redirect_page_id_opt));
+                self.backlinks.entry(to_page_id).or_default().push((from_page_id, redirect_page_id_opt));
+            }
+        }
+    }
+
+    fn add_redirect(&mut self, ext_page_id: i64, title: String, target_ext_page_id: i64) {
+        if let (Some(&page_id), Some(&target_page_id)) = (self.page_ids.get(&ext_page_id), self.page_ids.get(&target_ext_page_id)) {
+            if page_id != target_page_id {
+                self.redirects.insert(page_id, (target_page_id, title));
+            }
+        }
+    }
+
+    fn redirect_target(&self, ext_page_id: i64) -> Option<i64> {
+        self.page_ids
+            .get(&ext_page_id)
+            .and_then(|page_id| self.redirects.get(page_id))
+            .map(|&(target_page_id, _)| self.page(target_page_id).ext_page_id)
+    }
+
+    fn queue_seed(&mut self, ext_page_id: i64) {
+        let page_id = self.add_page(ext_page_id);
+
+        let page = self.page_mut(page_id);
+        match page.frontier_opt {
+            Some((FAILED, _, _)) | None => page.frontier_opt = Some((QUEUED, 0, 0)),
+            Some(_) => (),
+        }
+    }
+
+    fn queue_discovered(&mut self, crawled_ext_page_id: i64, discovered_ext_page_id: i64) {
+        if let (Some(&crawled_page_id), Some(&discovered_page_id)) = (self.page_ids.get(&crawled_ext_page_id), self.page_ids.get(&discovered_ext_page_id)) {
+            let depth = self.page(crawled_page_id).frontier_opt.map_or(0, |frontier| frontier.1) + 1;
+            let discovered_page = self.page_mut(discovered_page_id);
+            if discovered_page.frontier_opt.is_none() {
+                discovered_page.frontier_opt = Some((QUEUED, depth, 0));
+            }
+        }
+    }
+
+    fn take_queued(&mut self, max_in_flight: usize) -> Vec<i64> {
+        let limit = max_in_flight.saturating_sub(self.count_state(IN_FLIGHT) as usize);
+
+        let mut queued: Vec<(i64, i64)> = self.pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| match page.frontier_opt {
+                Some((QUEUED, depth, _)) => Some((depth, index as i64 + 1)),
+                _ => None,
+            })
+            .collect();
+        queued.sort();
+        queued.truncate(limit);
+
+        queued.into_iter()
+            .map(|(_, page_id)| {
+                let page = self.page_mut(page_id);
+                if let Some(ref mut frontier) = page.frontier_opt {
+                    frontier.0 = IN_FLIGHT;
+                    frontier.2 += 1;
+                }
+                page.ext_page_id
+            })
+            .collect()
+    }
+
+    fn reclaim_in_flight(&mut self) {
+        for page in &mut self.pages {
+            if let Some(ref mut frontier) = page.frontier_opt {
+                if frontier.0 == IN_FLIGHT {
+                    frontier.0 = if frontier.2 >= MAX_ATTEMPTS { FAILED } else { QUEUED };
+                }
+            }
+        }
+    }
+
+    fn requeue_or_fail(&mut self, ext_page_id: i64) {
+        let attempts = self.page_ids.get(&ext_page_id).and_then(|&page_id| self.page(page_id).frontier_opt).map_or(0, |frontier| frontier.2);
+        self.set_state(ext_page_id, if attempts >= MAX_ATTEMPTS { FAILED } else { QUEUED });
+    }
+
+    fn mark_failed(&mut self, ext_page_id: i64) {
+        self.set_state(ext_page_id, FAILED);
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            pages: self.pages.len() as i64,
+            crawled_pages: self.count_state(DONE),
+            links: self.link_ends.len() as i64,
+            redirects: self.redirects.len() as i64,
+            queued: self.count_state(QUEUED),
+            in_flight: self.count_state(IN_FLIGHT),
+            done: self.count_state(DONE),
+            failed: self.count_state(FAILED),
+            max_depth: self.pages.iter().filter_map(|page| page.frontier_opt.map(|frontier| frontier.1)).max().unwrap_or(0),
+        }
+    }
+}
@@ -1,81 +1,88 @@
 use main_x_page_id::MainToPageId;
 use page_id_x_querier::PageIdToQuerier;
-use rusqlite::Connection;
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use stats::print_stats;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError};
 use std::thread::{self, JoinHandle};
-use utils::open_database;
+use std::time::Duration;
+use store::GraphStore;
 
-pub fn start_pageid(buffer_size: usize, sender: Sender<PageIdToQuerier>, receiver: Receiver<MainToPageId>) -> JoinHandle<()> {
+// Pages are queued by the writer, which does not tell us, so with nothing to
+// hand out the frontier is looked at again this often.
+const IDLE_POLL_MILLIS: u64 = 100;
+
+pub fn start_pageid<S: GraphStore + Send + 'static>(store: S, buffer_size: usize, sender: SyncSender<PageIdToQuerier>, receiver: Receiver<MainToPageId>) -> JoinHandle<()> {
     thread::spawn(move || {
         let receiver = receiver;
         let sender = sender;
 
-        let conn = open_database();
+        let mut store = store;
 
-        let mut exit = false;
+        store.reclaim_in_flight();
 
-        let mut buffer: Vec<PageIdToQuerier> = vec![];
+        let mut paused = false;
 
-        let mut last_new_page_id_opt = None;
+        loop {
+            // Pausing only stops pages being handed out, the ones in flight
+            // still get written.
+            let taken = if paused { vec![] } else { store.take_queued(buffer_size) };
+            let idle = taken.is_empty();
 
-        while !exit {
-            // println!("Pageid Buffer Length: {}", pageids.len());
-            if buffer.len() < buffer_size {
-                match receiver.try_recv() {
-                    Ok(packet_enum) => {
-                        match packet_enum {
-                            MainToPageId::Exit => {
-                                exit = true;
-                            }
-                            MainToPageId::Packet(page_id) => {
-                                buffer.push(PageIdToQuerier::Packet(page_id));
-                            }
-                        }
-                    }
-                    Err(TryRecvError::Disconnected) => {}
-                    Err(TryRecvError::Empty) => {
-                        if let Some(target) = get_next_page_id_target(&conn) {
-                            if last_new_page_id_opt.is_some() {
-                                let last_new_page_id = last_new_page_id_opt.take().unwrap();
-                                if last_new_page_id != target {
-                                    last_new_page_id_opt = Some(target.clone());
-                                    buffer.push(PageIdToQuerier::Packet(target));
-                                } else {
-                                    last_new_page_id_opt = Some(last_new_page_id);
-                                }
-                            } else {
-                                last_new_page_id_opt = Some(target.clone());
-                                buffer.push(PageIdToQuerier::Packet(target));
-                            }
-                        }
+            for target in taken {
+                if sender.send(PageIdToQuerier::Packet(target)).is_err() {
+                    return;
+                }
+            }
 
-                        for packet in buffer.drain(..) {
-                            sender.send(packet).unwrap();
-                        }
-                    }
+            // A main that went away without saying exit is taken as exit.
+            let packet_opt = if paused {
+                Some(receiver.recv().unwrap_or(MainToPageId::Exit))
+            } else if idle {
+                match receiver.recv_timeout(Duration::from_millis(IDLE_POLL_MILLIS)) {
+                    Ok(packet_enum) => Some(packet_enum),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => Some(MainToPageId::Exit),
                 }
             } else {
-                for packet in buffer.drain(..) {
-                    sender.send(packet).unwrap();
+                match receiver.try_recv() {
+                    Ok(packet_enum) => Some(packet_enum),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => Some(MainToPageId::Exit),
                 }
-            }
-        }
+            };
+
+            let forward_opt = match packet_opt {
+                Some(MainToPageId::Exit) => break,
+                Some(MainToPageId::Packet(page_id)) => {
+                    store.queue_seed(page_id);
+                    None
+                }
+                Some(MainToPageId::SeedTitle(title)) => Some(PageIdToQuerier::SeedTitle(title)),
+                Some(MainToPageId::Pause) => {
+                    paused = true;
+                    println!("Paused");
+                    None
+                }
+                Some(MainToPageId::Resume) => {
+                    paused = false;
+                    println!("Resumed");
+                    None
+                }
+                Some(MainToPageId::Status) => {
+                    print_stats(&store.stats());
+                    println!("Paused: {}", paused);
+                    None
+                }
+                Some(MainToPageId::Flush) => Some(PageIdToQuerier::Flush),
+                None => None,
+            };
 
-        for packet in buffer.drain(..) {
-            sender.send(packet).unwrap();
+            if let Some(forward) = forward_opt {
+                if sender.send(forward).is_err() {
+                    return;
+                }
+            }
         }
 
-        sender.send(PageIdToQuerier::Exit).unwrap();
+        let _ = sender.send(PageIdToQuerier::Exit);
     })
 }
-
-fn get_next_page_id_target(conn: &Connection) -> Option<String> {
-    let mut stmt = conn.prepare("SELECT ext_page_id FROM page WHERE title IS NULL").unwrap();
-
-    let ext_page_id_iter = stmt.query_map(&[], |row| row.get(0)).unwrap();
-
-    for ext_page_id_result in ext_page_id_iter {
-        return Some(ext_page_id_result.unwrap());
-    }
-    None
-}
@@ -1,5 +1,7 @@
 #[derive(Debug)]
 pub enum PageIdToQuerier {
-    Packet(String),
+    Packet(i64),
+    SeedTitle(String),
+    Flush,
     Exit,
 }
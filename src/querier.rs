@@ -1,201 +1,503 @@
-use hyper::client::Client;
-use hyper::net::HttpsConnector;
-use hyper_native_tls::NativeTlsClient;
-use json::{self, JsonValue};
+use api_error::check_page;
 use page_id_x_querier::PageIdToQuerier;
 use querier_x_writer::QuerierToWriter;
-use std::io::Read;
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use response::{Alias, QueryResponse};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use transport::{Transport, encode_query, get_json};
 use utils::WikiRacerError;
 
-pub fn start_querier(buffer_size: usize, receiver: Receiver<PageIdToQuerier>, sender: Sender<QuerierToWriter>) -> JoinHandle<()> {
+pub const PAGE_IDS_PER_REQUEST: usize = 50;
+
+const BATCH_WAIT_MILLIS: u64 = 10;
+
+// The pages, links and redirects of a batch as the writer takes them, along
+// with the pages the API says are gone.
+type QueriedPages = (Vec<(Option<String>, i64)>, Vec<(i64, i64, Option<i64>)>, Vec<(i64, String, i64)>, Vec<(i64, WikiRacerError)>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkDirection {
+    Backlinks,
+    Links,
+    Both,
+}
+
+impl FromStr for LinkDirection {
+    type Err = WikiRacerError;
+
+    fn from_str(direction: &str) -> Result<LinkDirection, WikiRacerError> {
+        match direction {
+            "backlinks" => Ok(LinkDirection::Backlinks),
+            "links" => Ok(LinkDirection::Links),
+            "both" => Ok(LinkDirection::Both),
+            _ => Err(WikiRacerError::FormatError("Link direction was not backlinks, links or both")),
+        }
+    }
+}
+
+impl LinkDirection {
+    fn query_params(&self) -> &'static str {
+        match *self {
+            LinkDirection::Backlinks => "&prop=linkshere&lhprop=pageid|title|redirect&lhlimit=500&lhnamespace=0",
+            LinkDirection::Links => "&prop=info",
+            LinkDirection::Both => "&prop=info|linkshere&lhprop=pageid|title|redirect&lhlimit=500&lhnamespace=0",
+        }
+    }
+
+    fn follows_links(&self) -> bool {
+        *self != LinkDirection::Backlinks
+    }
+}
+
+// Starts one querier worker per transport. The workers take turns pulling
+// from the page id stage and share the writer, which gets a single `Exit`
+// once every one of them has stopped.
+pub fn start_querier<T: Transport + Send + 'static>(transports: Vec<T>, direction: LinkDirection, receiver: Receiver<PageIdToQuerier>, sender: SyncSender<QuerierToWriter>) -> JoinHandle<()> {
     thread::spawn(move || {
-        let mut buffer: Vec<QuerierToWriter> = vec![];
-        let receiver = receiver;
-        let sender = sender;
-
-        let ssl = NativeTlsClient::new().unwrap();
-        let connector = HttpsConnector::new(ssl);
-        let client = Client::with_connector(connector);
-
-        let mut exit = false;
-        while !exit {
-            if buffer.len() < buffer_size {
-                match receiver.try_recv() {
-                    Ok(packet_enum) => {
-                        match packet_enum {
-                            PageIdToQuerier::Exit => {
-                                exit = true;
-                            }
-                            PageIdToQuerier::Packet(page_id) => {
-                                let mut base_json = query_wikipedia(&client, page_id.clone(), None);
-                                let mut pages = vec![];
-                                let mut links = vec![];
-                                while let Some(hl_continue) = add_to_database(&mut pages, &mut links, &base_json).unwrap() {
-                                    base_json = query_wikipedia(&client, page_id.clone(), Some(hl_continue));
-                                }
-                                let packet = QuerierToWriter::Packet(pages, links);
-                                buffer.push(packet);
-                            }
-                        }
-                    }
-                    Err(TryRecvError::Disconnected) => {}
-                    Err(TryRecvError::Empty) => {
-                        for packet in buffer.drain(..) {
-                            sender.send(packet).unwrap();
-                        }
-                    }
+        let receiver = Arc::new(Mutex::new(receiver));
+        // Held for reading by every worker with messages in hand, so a flush
+        // can wait for whatever was taken before it.
+        let in_hand = Arc::new(RwLock::new(()));
+
+        let workers: Vec<JoinHandle<()>> = transports.into_iter()
+            .map(|transport| {
+                let receiver = receiver.clone();
+                let in_hand = in_hand.clone();
+                let sender = sender.clone();
+                thread::spawn(move || run_querier(transport, direction, &receiver, &in_hand, &sender))
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let _ = sender.send(QuerierToWriter::Exit);
+    })
+}
+
+// The page id stage drops its end after `Exit`, so only one worker sees the
+// `Exit` and the others stop on the channel closing.
+fn run_querier<T: Transport>(transport: T, direction: LinkDirection, receiver: &Mutex<Receiver<PageIdToQuerier>>, in_hand: &RwLock<()>, sender: &SyncSender<QuerierToWriter>) {
+    let mut exit = false;
+    while !exit {
+        let mut pending: Vec<i64> = vec![];
+        let mut seed_titles: Vec<String> = vec![];
+        let mut flush = false;
+
+        let in_hand_guard = {
+            // Waits for one page id, then gives the page id stage a moment to
+            // send the rest of what it took so they share a request.
+            let receiver = receiver.lock().unwrap();
+
+            let mut packet_opt = receiver.recv().ok();
+            loop {
+                match packet_opt {
+                    Some(PageIdToQuerier::Packet(page_id)) => pending.push(page_id),
+                    Some(PageIdToQuerier::SeedTitle(title)) => seed_titles.push(title),
+                    Some(PageIdToQuerier::Flush) => flush = true,
+                    Some(PageIdToQuerier::Exit) | None => exit = true,
                 }
-            } else {
-                for packet in buffer.drain(..) {
-                    sender.send(packet).unwrap();
+                if exit || flush || pending.len() >= PAGE_IDS_PER_REQUEST {
+                    break;
                 }
+                packet_opt = match receiver.recv_timeout(Duration::from_millis(BATCH_WAIT_MILLIS)) {
+                    Ok(packet_enum) => Some(packet_enum),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => None,
+                };
             }
+
+            in_hand.read().unwrap()
+        };
+
+        let mut packets = vec![];
+        if !seed_titles.is_empty() {
+            packets.extend(seed_batch(&transport, seed_titles));
+        }
+        if !pending.is_empty() {
+            packets.extend(query_batch(&transport, pending, direction));
         }
 
-        for packet in buffer.drain(..) {
-            sender.send(packet).unwrap();
+        for packet in packets {
+            if sender.send(packet).is_err() {
+                return;
+            }
         }
 
-        sender.send(QuerierToWriter::Exit).unwrap();
-    })
+        drop(in_hand_guard);
+
+        if flush {
+            let _all_sent = in_hand.write().unwrap();
+            if sender.send(QuerierToWriter::Flush).is_err() {
+                return;
+            }
+        }
+    }
 }
 
-fn query_wikipedia(client: &Client, pageids: String, lh_continue_opt: Option<String>) -> JsonValue {
-    let url = {
-        if let Some(continue_string) = lh_continue_opt {
-            format!("https://en.wikipedia.org/w/api.php?action=query&format=json&pageids={}&prop=linkshere&lhprop=pageid&lhlimit=500&lhnamespace=0&redirects{}", pageids, continue_string)
-        } else {
-            format!("https://en.wikipedia.org/w/api.php?action=query&format=json&pageids={}&prop=linkshere&lhprop=pageid&lhlimt=500&lhnamespace=0", pageids)
+// Seeds given by title are resolved here since this stage has the transport.
+fn seed_batch<T: Transport>(transport: &T, titles: Vec<String>) -> Vec<QuerierToWriter> {
+    let resolved_seeds = match resolve_seed_titles(transport, &titles) {
+        Ok(resolved_seeds) => resolved_seeds,
+        Err(err) => {
+            println!("Resolving seed titles failed: {}", err);
+            return vec![];
         }
     };
-    // println!("{:?}", url);
-    let mut resp = client.get(url.as_str())
-        .send()
-        .unwrap();
-    let mut body = vec![];
-    resp.read_to_end(&mut body).unwrap();
-    let string = String::from_utf8_lossy(&body);
-    // println!("{:?}", string);
-    json::parse(&string).unwrap()
-}
-
-fn add_to_database(out_pages: &mut Vec<(Option<String>, String)>, out_links: &mut Vec<(String, String)>, base: &JsonValue) -> Result<Option<String>, WikiRacerError> {
-    let mut continue_opt = None;
-
-    match base {
-        &JsonValue::Object(ref base_obj) => {
-            if let Some(json_continue) = base_obj.get("continue") {
-                match json_continue {
-                    &JsonValue::Object(ref json_continue_obj) => {
-                        let mut lhcontinue_opt = None;
-
-                        if let Some(lhcontinue) = json_continue_obj.get("lhcontinue") {
-                            match lhcontinue {
-                                &JsonValue::String(ref lhcontinue_string) => {
-                                    lhcontinue_opt = Some(lhcontinue_string.to_string());
-                                }
-                                &JsonValue::Short(ref lhcontinue_short) => {
-                                    lhcontinue_opt = Some(lhcontinue_short.to_string());
-                                }
-                                _ => (),
-                            }
-                        }
 
-                        if let Some(lhcontinue) = lhcontinue_opt {
-                            continue_opt = Some(format!("&lhcontinue={}&continue=||", lhcontinue));
+    let mut packets = vec![];
+    for title in titles {
+        match resolved_seeds.get(&title) {
+            Some(&(ref resolved_title, page_id)) => {
+                println!("Seeding {:?} as {:?} ({})", title, resolved_title, page_id);
+                packets.push(QuerierToWriter::Seed(page_id));
+            }
+            None => println!("Unknown seed title {:?}", title),
+        }
+    }
+    packets
+}
+
+// Queries all page ids in one request and splits the answer back into one
+// packet per page, so the writer commits and the frontier advances per page.
+fn query_batch<T: Transport>(transport: &T, page_ids: Vec<i64>, direction: LinkDirection) -> Vec<QuerierToWriter> {
+    let (pages, links, redirects, gone) = match query_pages(transport, join_page_ids(&page_ids), direction) {
+        Ok(pages_links_redirects_and_gone) => pages_links_redirects_and_gone,
+        // One bad id fails the whole request, so find it by asking one by one.
+        Err(WikiRacerError::InvalidPageId(_)) if page_ids.len() > 1 => {
+            return page_ids.into_iter().flat_map(|page_id| query_batch(transport, vec![page_id], direction)).collect();
+        }
+        Err(WikiRacerError::InvalidPageId(info)) => {
+            println!("Page {} is not a valid page id: {}", join_page_ids(&page_ids), info);
+            return page_ids.into_iter().map(QuerierToWriter::Missing).collect();
+        }
+        Err(err) => {
+            println!("Querying {} failed: {}", join_page_ids(&page_ids), err);
+            return page_ids.into_iter().map(QuerierToWriter::Failed).collect();
+        }
+    };
+
+    page_ids.into_iter()
+        .map(|page_id| {
+            let title_opt = pages.iter().filter(|page| page.1 == page_id).filter_map(|page| page.0.clone()).next();
+            match title_opt {
+                Some(title) => {
+                    println!("{:?}", title);
+                    let page_links: Vec<(i64, i64, Option<i64>)> = links.iter().filter(|link| link.0 == page_id || link.1 == page_id).cloned().collect();
+                    // Redirects of this page, and those its links have to be resolved through.
+                    let page_redirects: Vec<(i64, String, i64)> = redirects.iter()
+                        .filter(|redirect| redirect.0 == page_id || redirect.2 == page_id || page_links.iter().any(|link| link.0 == redirect.0))
+                        .cloned()
+                        .collect();
+                    let mut page_pages = vec![(Some(title), page_id)];
+                    page_pages.extend(page_links.iter().map(|link| if link.0 == page_id { (None, link.1) } else { (None, link.0) }));
+                    page_pages.extend(page_redirects.iter().flat_map(|redirect| vec![(None, redirect.0), (None, redirect.2)]));
+                    QuerierToWriter::Packet(page_pages, page_links, page_redirects)
+                }
+                None => {
+                    match gone.iter().find(|gone_page| gone_page.0 == page_id) {
+                        Some((_, err)) => {
+                            println!("Page {} is gone: {}", page_id, err);
+                            QuerierToWriter::Missing(page_id)
                         }
+                        // Neither a title nor a reason, so the response was not what we expected.
+                        None => QuerierToWriter::Failed(page_id),
                     }
-                    _ => (),
                 }
             }
-            // println!("{:?}", base_obj);
-            if let Some(query) = base_obj.get("query") {
-                // println!("{:?}", query);
-                match query {
-                    &JsonValue::Object(ref query_obj) => {
-                        // println!("{:?}", query_obj);
-                        if let Some(pages) = query_obj.get("pages") {
-                            // println!("{:?}", pages);
-                            match pages {
-                                &JsonValue::Object(ref pages_obj) => {
-                                    // println!("{:?}", pages_obj);
-                                    for page_entry in pages_obj.iter() {
-                                        let page_id = page_entry.0.to_string();
-                                        let page = page_entry.1;
-                                        // println!("{:?} --- {:?}", page_id, page);
-                                        match page {
-                                            &JsonValue::Object(ref page_obj) => {
-                                                // println!("{:?}", page_obj);
-                                                if let Some(page_title) = page_obj.get("title") {
-                                                    // println!("{:?}", page_title);
-                                                    match page_title {
-                                                        &JsonValue::String(ref page_title_string) => {
-                                                            // if continue_opt.is_none() {
-                                                            println!("{:?}", page_title_string);
-                                                            // }
-                                                            let title_str = page_title_string.to_string();
-                                                            out_pages.push((Some(title_str), page_id.clone()));
-                                                        }
-                                                        &JsonValue::Short(ref page_title_short) => {
-                                                            // if continue_opt.is_none() {
-                                                            println!("{:?}", page_title_short);
-                                                            // }
-                                                            let title_str = page_title_short.to_string();
-                                                            out_pages.push((Some(title_str), page_id.clone()));
-                                                        }
-                                                        _ => return Err(WikiRacerError::FormatError("Page Title was not a string or short")),
-                                                    }
-                                                }
-                                                if let Some(page_links_here) = page_obj.get("linkshere") {
-                                                    // println!("{:?}", page_links_here);
-                                                    match page_links_here {
-                                                        &JsonValue::Array(ref page_links_here_vec) => {
-                                                            // println!("{:?}", page_links_here_vec);
-                                                            for page_link_here in page_links_here_vec {
-                                                                // println!("{:?}", page_link_here);
-                                                                match page_link_here {
-                                                                    &JsonValue::Object(ref page_link_here_obj) => {
-                                                                        // println!("{:?}", page_link_here_obj);
-                                                                        if let Some(page_link_here_id) = page_link_here_obj.get("pageid") {
-                                                                            // println!("{:?}", page_link_here_id);
-                                                                            match page_link_here_id {
-                                                                                &JsonValue::Number(ref page_link_here_id_num) => {
-                                                                                    // println!("{:?}", page_link_here_id_num);
-                                                                                    if let Some(page_link_here_id_num_u64) = page_link_here_id_num.as_fixed_point_u64(0) {
-                                                                                        // println!("{:?}", page_link_here_id_num_u64);
-                                                                                        let other_page_id_string = page_link_here_id_num_u64.to_string();
-                                                                                        let other_page_id_str = other_page_id_string.to_string();
-                                                                                        out_pages.push((None, other_page_id_str.clone()));
-                                                                                        out_links.push((page_id.clone(), other_page_id_str.clone()));
-                                                                                    }
-                                                                                }
-                                                                                _ => return Err(WikiRacerError::FormatError("Page Link Here Id was not a number")),
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                    _ => return Err(WikiRacerError::FormatError("Page Link Here was not an object")),
-                                                                }
-                                                            }
-                                                        }
-                                                        _ => return Err(WikiRacerError::FormatError("Page Links Here was not an array")),
-                                                    }
-                                                }
-                                            }
-                                            _ => return Err(WikiRacerError::FormatError("Page was not an object")),
-                                        }
-                                    }
-                                }
-                                _ => return Err(WikiRacerError::FormatError("Pages was not an object")),
-                            }
-                        }
-                    }
-                    _ => return Err(WikiRacerError::FormatError("Query was not an object")),
+        })
+        .collect()
+}
+
+fn join_page_ids(page_ids: &[i64]) -> String {
+    page_ids.iter().map(|page_id| page_id.to_string()).collect::<Vec<String>>().join("|")
+}
+
+// Continued responses repeat every page of the batch but only carry the
+// linkshere entries after the continuation point, so the links of each page
+// are simply the union over all responses.
+fn query_pages<T: Transport>(transport: &T, page_ids: String, direction: LinkDirection) -> Result<QueriedPages, WikiRacerError> {
+    let mut response = query_wikipedia(transport, &page_ids, direction, &[])?;
+
+    let mut gone = vec![];
+    for page in &response.pages {
+        // Pages asked for by id are keyed by it, gone or not.
+        if let (Err(err), Ok(page_id)) = (check_page(page), page.key.parse()) {
+            gone.push((page_id, err));
+        }
+    }
+
+    let mut pages = vec![];
+    let mut links = vec![];
+    let mut redirects = vec![];
+    let mut linking_pages = vec![];
+    loop {
+        add_to_database(&mut pages, &mut links, &mut redirects, &response);
+        if direction.follows_links() {
+            for page in &response.pages {
+                if let (Some(title), Some(page_id)) = (&page.title, page.page_id) {
+                    linking_pages.push((title.clone(), page_id as i64, page.redirect));
                 }
             }
         }
-        _ => return Err(WikiRacerError::FormatError("Base was not an object")),
+        if response.continue_params.is_empty() {
+            break;
+        }
+        response = query_wikipedia(transport, &page_ids, direction, &response.continue_params)?;
+    }
+
+    linking_pages.sort();
+    linking_pages.dedup();
+
+    let mut linked_redirects = vec![];
+    for (title, page_id, is_redirect) in linking_pages {
+        for (linked_title, linked_page_id, linked_redirect) in query_linked_pages(transport, page_id)? {
+            pages.push((None, linked_page_id));
+            // The only link of a redirect page is its target.
+            if is_redirect {
+                redirects.push((page_id, title.clone(), linked_page_id));
+            } else {
+                links.push((linked_page_id, page_id, None));
+                if linked_redirect {
+                    linked_redirects.push((linked_page_id, linked_title));
+                }
+            }
+        }
+    }
+
+    linked_redirects.sort();
+    linked_redirects.dedup();
+
+    for (redirect_page_id, redirect_title, target_page_id) in resolve_redirects(transport, &linked_redirects)? {
+        pages.push((None, target_page_id));
+        redirects.push((redirect_page_id, redirect_title, target_page_id));
+    }
+
+    Ok((pages, links, redirects, gone))
+}
+
+// Redirects are never followed here. A redirect page is crawled as itself and
+// recorded as a redirect, and the writer moves links onto the target.
+fn query_wikipedia<T: Transport>(transport: &T, pageids: &str, direction: LinkDirection, continue_params: &[(String, String)]) -> Result<QueryResponse, WikiRacerError> {
+    let query = {
+        if continue_params.is_empty() {
+            format!("action=query&format=json&pageids={}{}", pageids, direction.query_params())
+        } else {
+            let continue_pairs: Vec<(&str, &str)> = continue_params.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+            format!("action=query&format=json&pageids={}{}&{}", pageids, direction.query_params(), encode_query(&continue_pairs))
+        }
+    };
+    QueryResponse::parse(&get_json(transport, &query)?)
+}
+
+// Lists the pages a page links to as (title, ext page id, is a redirect).
+// `prop=links` only names them, while generating the linked pages gives their
+// ids and redirect flags in the same response. A generator loses track of
+// which page linked where, so this asks about one page at a time.
+fn query_linked_pages<T: Transport>(transport: &T, page_id: i64) -> Result<Vec<(String, i64, bool)>, WikiRacerError> {
+    let page_id_string = page_id.to_string();
+    let mut linked_pages = vec![];
+    let mut continue_params: Vec<(String, String)> = vec![];
+
+    loop {
+        let mut params = vec![("action", "query"), ("format", "json"), ("generator", "links"), ("pageids", page_id_string.as_str()), ("gpllimit", "500"), ("gplnamespace", "0"), ("prop", "info")];
+        params.extend(continue_params.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+
+        let response = QueryResponse::parse(&get_json(transport, &encode_query(&params))?)?;
+
+        // Links to pages that do not exist come back without an id.
+        for page in &response.pages {
+            if let (Some(title), Some(linked_page_id)) = (&page.title, page.page_id) {
+                linked_pages.push((title.clone(), linked_page_id as i64, page.redirect));
+            }
+        }
+
+        continue_params = response.continue_params;
+
+        if continue_params.is_empty() {
+            break;
+        }
+    }
+
+    Ok(linked_pages)
+}
+
+// Finds the targets of redirect pages given as (ext page id, title), returned
+// as (redirect ext page id, redirect title, target ext page id). Asking with
+// `redirects` names the targets, but no longer the ids of the redirects,
+// hence the ids are passed in.
+fn resolve_redirects<T: Transport>(transport: &T, redirect_pages: &[(i64, String)]) -> Result<Vec<(i64, String, i64)>, WikiRacerError> {
+    let mut redirects = vec![];
+
+    for redirect_pages_chunk in redirect_pages.chunks(PAGE_IDS_PER_REQUEST) {
+        let redirect_titles: Vec<&str> = redirect_pages_chunk.iter().map(|redirect_page| redirect_page.1.as_str()).collect();
+        let query = encode_query(&[("action", "query"), ("format", "json"), ("redirects", ""), ("titles", &redirect_titles.join("|"))]);
+        let response = QueryResponse::parse(&get_json(transport, &query)?)?;
+
+        for &(redirect_page_id, ref redirect_title) in redirect_pages_chunk {
+            let target_title = follow_aliases(&response.redirects, redirect_title);
+            let target_page_id_opt = response.pages.iter().find(|page| page.title.as_ref().is_some_and(|title| title == target_title)).and_then(|page| page.page_id);
+            if let Some(target_page_id) = target_page_id_opt {
+                redirects.push((redirect_page_id, redirect_title.clone(), target_page_id as i64));
+            }
+        }
+    }
+
+    Ok(redirects)
+}
+
+// Resolves titles the way the search box would, through normalization and
+// redirects, to the (title, ext page id) of the page a reader ends up on.
+// Titles of missing pages are left out.
+fn resolve_seed_titles<T: Transport>(transport: &T, titles: &[String]) -> Result<HashMap<String, (String, i64)>, WikiRacerError> {
+    let mut seeds = HashMap::new();
+
+    for titles_chunk in titles.chunks(PAGE_IDS_PER_REQUEST) {
+        let query = encode_query(&[("action", "query"), ("format", "json"), ("redirects", ""), ("titles", &titles_chunk.join("|"))]);
+        let response = QueryResponse::parse(&get_json(transport, &query)?)?;
+
+        for title in titles_chunk {
+            let resolved_title = follow_aliases(&response.redirects, follow_aliases(&response.normalized, title));
+            let page_opt = response.pages.iter().find(|page| page.title.as_ref().is_some_and(|page_title| page_title == resolved_title));
+            if let Some(page_id) = page_opt.and_then(|page| page.page_id) {
+                seeds.insert(title.clone(), (resolved_title.to_string(), page_id as i64));
+            }
+        }
+    }
+
+    Ok(seeds)
+}
+
+// Follows a chain of aliases, as double redirects come back one hop per alias.
+fn follow_aliases<'a>(aliases: &'a [Alias], title: &'a str) -> &'a str {
+    let mut current = title;
+    for _ in 0..aliases.len() {
+        match aliases.iter().find(|alias| alias.from == current) {
+            Some(alias) => current = &alias.to,
+            None => break,
+        }
+    }
+    current
+}
+
+fn add_to_database(out_pages: &mut Vec<(Option<String>, i64)>, out_links: &mut Vec<(i64, i64, Option<i64>)>, out_redirects: &mut Vec<(i64, String, i64)>, response: &QueryResponse) {
+    for page in &response.pages {
+        let page_id = match page.page_id {
+            Some(page_id) => page_id as i64,
+            None => continue,
+        };
+
+        if let Some(ref title) = page.title {
+            out_pages.push((Some(title.clone()), page_id));
+        }
+
+        for link_here in &page.linkshere {
+            if let Some(other_page_id) = link_here.page_id {
+                out_pages.push((None, other_page_id as i64));
+                match (link_here.redirect, &link_here.title) {
+                    (true, Some(other_title)) => out_redirects.push((other_page_id as i64, other_title.clone(), page_id)),
+                    _ => out_links.push((page_id, other_page_id as i64, None)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use querier_x_writer::QuerierToWriter;
+    use solver::{PathResult, find_path};
+    use sqlite_store::SqliteStore;
+    use std::path::Path;
+    use std::sync::mpsc::sync_channel;
+    use store::GraphStore;
+    use transport::FixtureTransport;
+    use utils::open_or_create_database;
+    use writer::start_writer;
+
+    fn crawl_fixtures() -> FixtureTransport {
+        FixtureTransport::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/crawl"))).unwrap()
+    }
+
+    // Hands the packets to a writer and takes the store back once it is done with them.
+    fn write_packets(store: SqliteStore, packets: Vec<QuerierToWriter>) -> SqliteStore {
+        let (sender, receiver) = sync_channel::<QuerierToWriter>(packets.len() + 1);
+        let writer = start_writer(store, receiver);
+        for packet in packets {
+            sender.send(packet).unwrap();
+        }
+        sender.send(QuerierToWriter::Exit).unwrap();
+        writer.join().unwrap()
+    }
+
+    fn path_labels(result: PathResult) -> Vec<String> {
+        match result {
+            PathResult::Found(steps) => steps.iter().map(|step| step.to_string()).collect(),
+            other => panic!("Expected a path, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn crawls_fixtures_into_the_store() {
+        let fixtures = crawl_fixtures();
+        let mut store = SqliteStore::new(open_or_create_database(":memory:").unwrap());
+
+        let seeds = seed_batch(&fixtures, vec!["a".to_string(), "Nope".to_string(), "R".to_string()]);
+        match seeds.as_slice() {
+            [QuerierToWriter::Seed(1), QuerierToWriter::Seed(1)] => {}
+            other => panic!("Unexpected seeds {:?}", other),
+        }
+        store = write_packets(store, seeds);
+
+        loop {
+            let page_ids = store.take_queued(10);
+            if page_ids.is_empty() {
+                break;
+            }
+            let packets = query_batch(&fixtures, page_ids, LinkDirection::Backlinks);
+            assert!(packets.iter().all(|packet| matches!(*packet, QuerierToWriter::Packet(..))));
+            store = write_packets(store, packets);
+        }
+
+        let stats = store.stats();
+        assert_eq!((stats.done, stats.queued, stats.failed), (5, 0, 0));
+        assert_eq!((stats.links, stats.redirects), (3, 1));
+
+        assert_eq!(path_labels(find_path(&store, "P", "A")), vec!["P", "B", "A"]);
+        assert_eq!(path_labels(find_path(&store, "Q", "A")), vec!["Q", "R (redirect) -> A"]);
+        match find_path(&store, "A", "P") {
+            PathResult::NoPath => {}
+            other => panic!("Expected no path, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pages_through_generated_links() {
+        let fixtures = FixtureTransport::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/links"))).unwrap();
+
+        let packets = query_batch(&fixtures, vec![1, 2], LinkDirection::Links);
+        let links_and_redirects: Vec<_> = packets.iter()
+            .map(|packet| match *packet {
+                QuerierToWriter::Packet(_, ref links, ref redirects) => (links.clone(), redirects.clone()),
+                ref other => panic!("Unexpected packet {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(links_and_redirects,
+                   vec![(vec![(3, 1, None), (4, 1, None), (5, 1, None)], vec![(2, "R".to_string(), 1), (5, "S".to_string(), 3)]), (vec![], vec![(2, "R".to_string(), 1)])]);
+    }
+
+    #[test]
+    fn fails_pages_without_a_fixture() {
+        match query_batch(&crawl_fixtures(), vec![7], LinkDirection::Backlinks).as_slice() {
+            [QuerierToWriter::Failed(7)] => {}
+            other => panic!("Unexpected packets {:?}", other),
+        }
     }
-    Ok(continue_opt)
 }
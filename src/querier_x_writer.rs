@@ -1,5 +1,14 @@
 #[derive(Debug)]
 pub enum QuerierToWriter {
-    Packet(Vec<(Option<String>, String)>, Vec<(String, String)>),
+    // Pages as (title, ext page id), links as (to, from, position among the
+    // links of from when known) and redirects as (redirect ext page id,
+    // redirect title, target ext page id).
+    Packet(Vec<(Option<String>, i64)>, Vec<(i64, i64, Option<i64>)>, Vec<(i64, String, i64)>),
+    Failed(i64),
+    Missing(i64),
+    // The ext page id of a seed resolved from the title it was given by.
+    Seed(i64),
+    // Sent after everything that was in flight when a flush was asked for.
+    Flush,
     Exit,
 }
@@ -0,0 +1,276 @@
+use json::JsonValue;
+use utils::WikiRacerError;
+
+// The parts of an `action=query` response the crawler and the live search
+// read, parsed once so neither has to walk the JSON themselves.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryResponse {
+    pub pages: Vec<Page>,
+    pub normalized: Vec<Alias>,
+    pub redirects: Vec<Alias>,
+    pub continue_params: Vec<(String, String)>,
+    pub batchcomplete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Page {
+    // The key of the page in `query.pages`, negative for missing titles.
+    pub key: String,
+    pub page_id: Option<u64>,
+    pub title: Option<String>,
+    pub missing: bool,
+    // Only known when `prop=info` was asked for.
+    pub redirect: bool,
+    pub invalid_reason: Option<String>,
+    pub linkshere: Vec<LinkedPage>,
+    pub links: Vec<LinkedPage>,
+}
+
+// An entry of `linkshere` or `links`, which carry the page id, the title or
+// both depending on the requested props.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LinkedPage {
+    pub page_id: Option<u64>,
+    pub title: Option<String>,
+    // Set on `linkshere` entries that are redirects, given `lhprop=redirect`.
+    pub redirect: bool,
+}
+
+// An entry of `normalized` or `redirects`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Alias {
+    pub from: String,
+    pub to: String,
+}
+
+impl QueryResponse {
+    pub fn parse(base: &JsonValue) -> Result<QueryResponse, WikiRacerError> {
+        if !base.is_object() {
+            return Err(WikiRacerError::FormatError("Base was not an object"));
+        }
+
+        let mut response = QueryResponse {
+            batchcomplete: base.has_key("batchcomplete"),
+            ..QueryResponse::default()
+        };
+
+        let json_continue = &base["continue"];
+        if !json_continue.is_null() {
+            if !json_continue.is_object() {
+                return Err(WikiRacerError::FormatError("Continue was not an object"));
+            }
+            for (key, value) in json_continue.entries() {
+                match value.as_str() {
+                    Some(value_str) => response.continue_params.push((key.to_string(), value_str.to_string())),
+                    None => response.continue_params.push((key.to_string(), value.dump())),
+                }
+            }
+        }
+
+        let query = &base["query"];
+        if query.is_null() {
+            return Ok(response);
+        }
+        if !query.is_object() {
+            return Err(WikiRacerError::FormatError("Query was not an object"));
+        }
+
+        response.normalized = parse_aliases(&query["normalized"])?;
+        response.redirects = parse_aliases(&query["redirects"])?;
+
+        let pages = &query["pages"];
+        if !pages.is_null() {
+            if !pages.is_object() {
+                return Err(WikiRacerError::FormatError("Pages was not an object"));
+            }
+            for (key, page) in pages.entries() {
+                response.pages.push(Page::parse(key, page)?);
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+impl Page {
+    fn parse(key: &str, page: &JsonValue) -> Result<Page, WikiRacerError> {
+        if !page.is_object() {
+            return Err(WikiRacerError::FormatError("Page was not an object"));
+        }
+
+        Ok(Page {
+            key: key.to_string(),
+            page_id: parse_page_id(&page["pageid"], "Page Id was not a number")?,
+            title: parse_title(&page["title"], "Page Title was not a string or short")?,
+            missing: page.has_key("missing"),
+            redirect: page.has_key("redirect"),
+            invalid_reason: if page.has_key("invalid") { Some(page["invalidreason"].as_str().unwrap_or("invalid").to_string()) } else { None },
+            linkshere: parse_linked_pages(&page["linkshere"], "Page Links Here was not an array")?,
+            links: parse_linked_pages(&page["links"], "Page Links was not an array")?,
+        })
+    }
+}
+
+fn parse_linked_pages(linked_pages: &JsonValue, not_array: &'static str) -> Result<Vec<LinkedPage>, WikiRacerError> {
+    if linked_pages.is_null() {
+        return Ok(vec![]);
+    }
+    if !linked_pages.is_array() {
+        return Err(WikiRacerError::FormatError(not_array));
+    }
+
+    let mut out = vec![];
+    for linked_page in linked_pages.members() {
+        if !linked_page.is_object() {
+            return Err(WikiRacerError::FormatError("Linked Page was not an object"));
+        }
+        out.push(LinkedPage {
+            page_id: parse_page_id(&linked_page["pageid"], "Linked Page Id was not a number")?,
+            title: parse_title(&linked_page["title"], "Linked Page Title was not a string or short")?,
+            redirect: linked_page.has_key("redirect"),
+        });
+    }
+    Ok(out)
+}
+
+fn parse_aliases(aliases: &JsonValue) -> Result<Vec<Alias>, WikiRacerError> {
+    if aliases.is_null() {
+        return Ok(vec![]);
+    }
+    if !aliases.is_array() {
+        return Err(WikiRacerError::FormatError("Aliases was not an array"));
+    }
+
+    let mut out = vec![];
+    for alias in aliases.members() {
+        match (alias["from"].as_str(), alias["to"].as_str()) {
+            (Some(from), Some(to)) => {
+                out.push(Alias {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+            }
+            _ => return Err(WikiRacerError::FormatError("Alias was not a pair of strings")),
+        }
+    }
+    Ok(out)
+}
+
+fn parse_page_id(page_id: &JsonValue, not_number: &'static str) -> Result<Option<u64>, WikiRacerError> {
+    if page_id.is_null() {
+        return Ok(None);
+    }
+    match page_id.as_u64() {
+        Some(page_id_u64) => Ok(Some(page_id_u64)),
+        None => Err(WikiRacerError::FormatError(not_number)),
+    }
+}
+
+fn parse_title(title: &JsonValue, not_string: &'static str) -> Result<Option<String>, WikiRacerError> {
+    if title.is_null() {
+        return Ok(None);
+    }
+    match title.as_str() {
+        Some(title_str) => Ok(Some(title_str.to_string())),
+        None => Err(WikiRacerError::FormatError(not_string)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json;
+    use std::fs::File;
+    use std::io::Read;
+
+    // Parses a response saved under `fixtures/responses`.
+    fn parse_saved(name: &str) -> QueryResponse {
+        let mut string = String::new();
+        File::open(format!("{}/fixtures/responses/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap().read_to_string(&mut string).unwrap();
+        QueryResponse::parse(&json::parse(&string).unwrap()).unwrap()
+    }
+
+    fn linked(page_id_opt: Option<u64>, title: &str, redirect: bool) -> LinkedPage {
+        LinkedPage {
+            page_id: page_id_opt,
+            title: Some(title.to_string()),
+            redirect,
+        }
+    }
+
+    fn alias(from: &str, to: &str) -> Alias {
+        Alias {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_linkshere_with_continue() {
+        let response = parse_saved("linkshere_continue.json");
+
+        assert!(!response.batchcomplete);
+        assert_eq!(response.continue_params,
+                   vec![("lhcontinue".to_string(), "736|30411".to_string()), ("continue".to_string(), "||".to_string())]);
+        assert_eq!(response.pages.len(), 1);
+
+        let page = &response.pages[0];
+        assert_eq!((page.key.as_str(), page.page_id, page.title.as_deref()), ("736", Some(736), Some("Albert Einstein")));
+        assert_eq!(page.linkshere,
+                   vec![linked(Some(303), "Alabama", false), linked(Some(2853), "Einstein", true), linked(Some(30410), "Theory of relativity", false)]);
+        assert!(page.links.is_empty());
+    }
+
+    #[test]
+    fn parses_links_with_continue_and_info() {
+        let response = parse_saved("links_continue.json");
+
+        assert_eq!(response.continue_params,
+                   vec![("plcontinue".to_string(), "9228|0|Aarau".to_string()), ("continue".to_string(), "||".to_string())]);
+        assert_eq!(response.pages.iter().map(|page| (page.page_id, page.redirect)).collect::<Vec<_>>(), vec![(Some(9228), true), (Some(736), false)]);
+        assert_eq!(response.pages[0].links, vec![linked(None, "Albert Einstein", false)]);
+        assert_eq!(response.pages[1].links, vec![linked(None, "Aachen", false), linked(None, "Aarau", false)]);
+    }
+
+    #[test]
+    fn parses_normalized_redirects_missing_and_invalid_titles() {
+        let response = parse_saved("titles_redirects.json");
+
+        assert!(response.batchcomplete);
+        assert!(response.continue_params.is_empty());
+        assert_eq!(response.normalized, vec![alias("albert_einstein", "Albert einstein")]);
+        assert_eq!(response.redirects, vec![alias("Albert einstein", "Albert Einstein")]);
+
+        let summary: Vec<(&str, Option<u64>, bool, bool)> = response.pages
+            .iter()
+            .map(|page| (page.key.as_str(), page.page_id, page.missing, page.invalid_reason.is_some()))
+            .collect();
+        assert_eq!(summary, vec![("-1", None, true, false), ("-2", None, false, true), ("736", Some(736), false, false)]);
+    }
+
+    #[test]
+    fn parses_missing_page_ids() {
+        let response = parse_saved("pageids_missing.json");
+
+        let missing: Vec<&Page> = response.pages.iter().filter(|page| page.missing).collect();
+        assert_eq!(missing.len(), 1);
+        assert_eq!((missing[0].page_id, &missing[0].title), (Some(999999999), &None));
+    }
+
+    #[test]
+    fn parses_error_responses_without_query() {
+        let response = parse_saved("error_badinteger.json");
+
+        assert_eq!(response, QueryResponse::default());
+    }
+
+    #[test]
+    fn rejects_unexpected_shapes() {
+        for body in &[r#"[]"#, r#"{"continue":"||"}"#, r#"{"query":{"pages":[]}}"#, r#"{"query":{"pages":{"1":{"pageid":"1"}}}}"#, r#"{"query":{"redirects":[{"from":"A"}]}}"#] {
+            match QueryResponse::parse(&json::parse(body).unwrap()) {
+                Err(WikiRacerError::FormatError(_)) => {}
+                other => panic!("Expected a format error for {}, got {:?}", body, other),
+            }
+        }
+    }
+}
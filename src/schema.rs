@@ -0,0 +1,379 @@
+use frontier::{DONE, FAILED, QUEUED};
+use rusqlite::Connection;
+use utils::WikiRacerError;
+
+// Every change to the schema, oldest first. A database at version `n` has
+// had the first `n` applied, so a new change only ever goes at the end.
+const MIGRATIONS: &[fn(&Connection)] = &[create_page_and_link, create_frontier, create_redirect, add_link_position, create_checkpoint, create_indexes, make_ext_page_ids_integers, create_link_from_index];
+
+pub const SCHEMA_VERSION: i64 = 8;
+
+// Brings a database, empty or not, up to `SCHEMA_VERSION`. Each migration
+// commits together with the version it reaches, so one that fails leaves
+// the database at the version before it. Databases from a newer build are
+// left untouched.
+pub fn migrate(conn: &mut Connection) -> Result<(), WikiRacerError> {
+    if !has_table(conn, "schema_version") {
+        let version = legacy_version(conn);
+        let trans = conn.transaction().unwrap();
+        trans.execute("CREATE TABLE schema_version (version INTEGER NOT NULL)", &[]).unwrap();
+        trans.execute("INSERT INTO schema_version (version) VALUES (?1)", &[&version]).unwrap();
+        trans.commit().unwrap();
+    }
+
+    let version: i64 = conn.query_row("SELECT version FROM schema_version", &[], |row| row.get(0)).unwrap();
+    if version > SCHEMA_VERSION {
+        return Err(WikiRacerError::SchemaTooNew(version, SCHEMA_VERSION));
+    }
+    if version > 0 && version < SCHEMA_VERSION {
+        println!("Upgrading database from schema version {} to {}", version, SCHEMA_VERSION);
+    }
+
+    // A migration that rebuilds a table drops the one the others refer to,
+    // which foreign keys would refuse. They cannot be switched inside a
+    // transaction, so they are off for the whole run.
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", &[], |row| row.get(0)).unwrap();
+    conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let trans = conn.transaction().unwrap();
+        migration(&trans);
+        trans.execute("UPDATE schema_version SET version = ?1", &[&(index as i64 + 1)]).unwrap();
+        trans.commit().unwrap();
+    }
+
+    if foreign_keys {
+        conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+    }
+
+    Ok(())
+}
+
+// Databases from before versioning are placed by what they already have.
+// Every build created everything the ones before it did, so what is there is
+// always a run of migrations from the first.
+fn legacy_version(conn: &Connection) -> i64 {
+    let applied = [has_table(conn, "page"),
+                   has_table(conn, "frontier"),
+                   has_table(conn, "redirect"),
+                   has_column(conn, "link", "position"),
+                   has_table(conn, "checkpoint"),
+                   has_index(conn, "page_ext_page_id")];
+
+    applied.iter().take_while(|&&present| present).count() as i64
+}
+
+fn create_page_and_link(conn: &Connection) {
+    conn.execute("CREATE TABLE page (
+                    page_id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                    title           TEXT,
+                    ext_page_id     TEXT
+                )",
+                 &[])
+        .unwrap();
+
+    conn.execute("CREATE TABLE link (
+                    link_id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                    to_page_id      INTEGER NOT NULL,
+                    from_page_id    INTEGER NOT NULL,
+                    length          INTEGER NOT NULL,
+                    CONSTRAINT Link_FK1 FOREIGN KEY (to_page_id) REFERENCES page(page_id),
+                    CONSTRAINT Link_FK2 FOREIGN KEY (from_page_id) REFERENCES page(page_id)
+                )",
+                 &[])
+        .unwrap();
+}
+
+// The frontier of a database crawled before it existed is derived from the
+// page table: pages with a title have been crawled, the rest are still queued.
+fn create_frontier(conn: &Connection) {
+    conn.execute("CREATE TABLE frontier (
+                    page_id         INTEGER PRIMARY KEY,
+                    state           TEXT NOT NULL,
+                    depth           INTEGER NOT NULL,
+                    attempts        INTEGER NOT NULL,
+                    CONSTRAINT Frontier_FK1 FOREIGN KEY (page_id) REFERENCES page(page_id)
+                )",
+                 &[])
+        .unwrap();
+
+    conn.execute("CREATE INDEX frontier_state_depth ON frontier (state, depth)", &[]).unwrap();
+
+    conn.execute("INSERT INTO frontier (page_id, state, depth, attempts) SELECT page_id, CASE WHEN title IS NULL THEN ?1 ELSE ?2 END, 0, 0 FROM page",
+                 &[&QUEUED, &DONE])
+        .unwrap();
+}
+
+// Links stored before this were all taken as direct.
+fn create_redirect(conn: &Connection) {
+    conn.execute("CREATE TABLE redirect (
+                    page_id         INTEGER PRIMARY KEY,
+                    target_page_id  INTEGER NOT NULL,
+                    title           TEXT NOT NULL,
+                    CONSTRAINT Redirect_FK1 FOREIGN KEY (page_id) REFERENCES page(page_id),
+                    CONSTRAINT Redirect_FK2 FOREIGN KEY (target_page_id) REFERENCES page(page_id)
+                )",
+                 &[])
+        .unwrap();
+
+    conn.execute("ALTER TABLE link ADD COLUMN redirect_page_id INTEGER REFERENCES page(page_id)", &[]).unwrap();
+}
+
+// Only dump imports know where on the page a link is, everything stored
+// before them leaves the position empty.
+fn add_link_position(conn: &Connection) {
+    conn.execute("ALTER TABLE link ADD COLUMN position INTEGER", &[]).unwrap();
+}
+
+fn create_checkpoint(conn: &Connection) {
+    conn.execute("CREATE TABLE checkpoint (
+                    checkpoint_id   INTEGER PRIMARY KEY AUTOINCREMENT,
+                    time            TEXT NOT NULL,
+                    queued          INTEGER NOT NULL,
+                    done            INTEGER NOT NULL,
+                    failed          INTEGER NOT NULL
+                )",
+                 &[])
+        .unwrap();
+}
+
+// Links could be stored twice before the index on their ends, only the first
+// is kept.
+fn create_indexes(conn: &Connection) {
+    conn.execute("DELETE FROM link WHERE link_id NOT IN (SELECT MIN(link_id) FROM link GROUP BY to_page_id, from_page_id)", &[]).unwrap();
+
+    conn.execute("CREATE UNIQUE INDEX page_ext_page_id ON page (ext_page_id)", &[]).unwrap();
+    conn.execute("CREATE UNIQUE INDEX link_to_from ON link (to_page_id, from_page_id)", &[]).unwrap();
+}
+
+// Ext page ids used to be stored as text, so one page could be stored twice,
+// say as "0123" and "123". Such pages are merged onto the first of them, and
+// ids that are no number at all are cleared, failing the pages that were yet
+// to be crawled. The table is rebuilt, a column type cannot be changed in place.
+fn make_ext_page_ids_integers(conn: &Connection) {
+    conn.execute_batch("CREATE TEMPORARY TABLE numeric_page AS
+                          SELECT page_id, CAST(ext_page_id AS INTEGER) AS ext_page_id FROM page
+                          WHERE ext_page_id <> '' AND ext_page_id NOT GLOB '*[^0-9]*';
+
+                        CREATE TEMPORARY TABLE page_merge AS
+                          SELECT numeric_page.page_id, kept_page.page_id AS kept_page_id FROM numeric_page
+                          JOIN (SELECT MIN(page_id) AS page_id, ext_page_id FROM numeric_page GROUP BY ext_page_id) AS kept_page
+                            ON kept_page.ext_page_id = numeric_page.ext_page_id
+                          WHERE numeric_page.page_id <> kept_page.page_id;
+
+                        UPDATE page SET title = (SELECT merged_page.title FROM page_merge
+                                                 JOIN page AS merged_page ON merged_page.page_id = page_merge.page_id
+                                                 WHERE page_merge.kept_page_id = page.page_id AND merged_page.title IS NOT NULL)
+                          WHERE title IS NULL AND page_id IN (SELECT kept_page_id FROM page_merge);
+
+                        UPDATE OR IGNORE link SET to_page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = link.to_page_id)
+                          WHERE to_page_id IN (SELECT page_id FROM page_merge);
+                        UPDATE OR IGNORE link SET from_page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = link.from_page_id)
+                          WHERE from_page_id IN (SELECT page_id FROM page_merge);
+                        UPDATE link SET redirect_page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = link.redirect_page_id)
+                          WHERE redirect_page_id IN (SELECT page_id FROM page_merge);
+                        DELETE FROM link WHERE to_page_id = from_page_id
+                          OR to_page_id IN (SELECT page_id FROM page_merge) OR from_page_id IN (SELECT page_id FROM page_merge);
+
+                        UPDATE OR IGNORE redirect SET page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = redirect.page_id)
+                          WHERE page_id IN (SELECT page_id FROM page_merge);
+                        UPDATE redirect SET target_page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = redirect.target_page_id)
+                          WHERE target_page_id IN (SELECT page_id FROM page_merge);
+                        DELETE FROM redirect WHERE page_id = target_page_id OR page_id IN (SELECT page_id FROM page_merge);
+
+                        UPDATE OR IGNORE frontier SET page_id = (SELECT kept_page_id FROM page_merge WHERE page_merge.page_id = frontier.page_id)
+                          WHERE page_id IN (SELECT page_id FROM page_merge);
+                        DELETE FROM frontier WHERE page_id IN (SELECT page_id FROM page_merge);
+
+                        CREATE TABLE page_integer (
+                          page_id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                          title           TEXT,
+                          ext_page_id     INTEGER
+                        );
+                        INSERT INTO page_integer (page_id, title, ext_page_id)
+                          SELECT page.page_id, page.title, numeric_page.ext_page_id FROM page
+                          LEFT JOIN numeric_page ON numeric_page.page_id = page.page_id
+                          WHERE page.page_id NOT IN (SELECT page_id FROM page_merge);
+                        DROP TABLE page;
+                        ALTER TABLE page_integer RENAME TO page;
+                        CREATE UNIQUE INDEX page_ext_page_id ON page (ext_page_id);
+
+                        DROP TABLE numeric_page;
+                        DROP TABLE page_merge;")
+        .unwrap();
+
+    conn.execute("UPDATE frontier SET state = ?1 WHERE state <> ?2 AND page_id IN (SELECT page_id FROM page WHERE ext_page_id IS NULL)",
+                 &[&FAILED, &DONE])
+        .unwrap();
+}
+
+// The path finder asks for the links of one page at a time.
+fn create_link_from_index(conn: &Connection) {
+    conn.execute("CREATE INDEX link_from ON link (from_page_id)", &[]).unwrap();
+}
+
+fn has_table(conn: &Connection, name: &str) -> bool {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", &[&name], |row| row.get(0)).unwrap();
+    count > 0
+}
+
+fn has_index(conn: &Connection, name: &str) -> bool {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1", &[&name], |row| row.get(0)).unwrap();
+    count > 0
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    conn.prepare(&format!("SELECT {} FROM {}", column, table)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::types::FromSql;
+    use utils::open_database;
+
+    // A database as the first `version` migrations leave it, with two pages
+    // and a link between them stored by the first build, and without the
+    // version table when `legacy`.
+    fn database_at(version: usize, legacy: bool) -> Connection {
+        let conn = open_database(":memory:");
+        // As in `migrate`, rebuilding the page table needs foreign keys off.
+        let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", &[], |row| row.get(0)).unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        for (index, migration) in MIGRATIONS[..version].iter().enumerate() {
+            migration(&conn);
+            if index == 0 {
+                conn.execute_batch("INSERT INTO page (title, ext_page_id) VALUES ('Albert Einstein', '736');
+                                    INSERT INTO page (title, ext_page_id) VALUES (NULL, '303');
+                                    INSERT INTO link (to_page_id, from_page_id, length) VALUES (2, 1, 1);")
+                    .unwrap();
+            }
+        }
+        if foreign_keys {
+            conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+        }
+        if !legacy {
+            conn.execute_batch(&format!("CREATE TABLE schema_version (version INTEGER NOT NULL);
+                                         INSERT INTO schema_version (version) VALUES ({});",
+                                        version))
+                .unwrap();
+        }
+        conn
+    }
+
+    fn schema_version(conn: &Connection) -> i64 {
+        conn.query_row("SELECT version FROM schema_version", &[], |row| row.get(0)).unwrap()
+    }
+
+    fn rows<T: FromSql>(conn: &Connection, sql: &str) -> Vec<T> {
+        let mut stmt = conn.prepare(sql).unwrap();
+        let row_iter = stmt.query_map(&[], |row| row.get(0)).unwrap();
+        row_iter.map(|row_result| row_result.unwrap()).collect()
+    }
+
+    fn schema(conn: &Connection) -> Vec<String> {
+        rows(conn, "SELECT type || ' ' || name || ' ' || IFNULL(sql, '') FROM sqlite_master ORDER BY name")
+    }
+
+    // A page as (title, ext page id, frontier state).
+    type PageRow = (Option<String>, i64, String);
+
+    fn pages_and_links(conn: &Connection) -> (Vec<PageRow>, Vec<(i64, i64)>) {
+        let mut page_stmt = conn.prepare("SELECT page.title, page.ext_page_id, frontier.state FROM page
+                                          JOIN frontier ON frontier.page_id = page.page_id
+                                          ORDER BY page.page_id")
+            .unwrap();
+        let pages = page_stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap().map(|row_result| row_result.unwrap()).collect();
+        let mut link_stmt = conn.prepare("SELECT to_page_id, from_page_id FROM link ORDER BY link_id").unwrap();
+        let links = link_stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap().map(|row_result| row_result.unwrap()).collect();
+        (pages, links)
+    }
+
+    #[test]
+    fn places_legacy_databases_by_what_they_have() {
+        for version in 0..7 {
+            assert_eq!(legacy_version(&database_at(version, true)), version as i64);
+        }
+    }
+
+    #[test]
+    fn migrates_every_version_keeping_data() {
+        let expected = (vec![(Some("Albert Einstein".to_string()), 736, DONE.to_string()), (None, 303, QUEUED.to_string())], vec![(2, 1)]);
+
+        let mut current = database_at(MIGRATIONS.len(), false);
+        let current_schema = schema(&current);
+        migrate(&mut current).unwrap();
+        assert_eq!(pages_and_links(&current), expected);
+
+        for &legacy in &[true, false] {
+            // Versioning came after the sixth migration, so legacy databases stop there.
+            let last_version = if legacy { 6 } else { MIGRATIONS.len() - 1 };
+            for version in 1..last_version + 1 {
+                let mut conn = database_at(version, legacy);
+                migrate(&mut conn).unwrap();
+
+                assert_eq!(schema_version(&conn), SCHEMA_VERSION, "from version {}", version);
+                assert_eq!(schema(&conn), current_schema, "from version {}", version);
+                assert_eq!(pages_and_links(&conn), expected, "from version {}", version);
+            }
+        }
+    }
+
+    #[test]
+    fn creates_empty_databases_and_reopens_them_unchanged() {
+        let mut conn = open_database(":memory:");
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn), SCHEMA_VERSION);
+
+        let migrated_schema = schema(&conn);
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn), SCHEMA_VERSION);
+        assert_eq!(schema(&conn), migrated_schema);
+    }
+
+    #[test]
+    fn merges_duplicate_ext_page_ids_and_clears_others() {
+        let conn = open_database(":memory:");
+        conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        for migration in &MIGRATIONS[..6] {
+            migration(&conn);
+        }
+        // 2 is 1 and 4 is 3 written differently, so is 9 of 8, which was
+        // never put on the frontier. 5 and 6 have no number for an id.
+        conn.execute_batch("INSERT INTO page (page_id, title, ext_page_id) VALUES (1, 'A', '0123'), (2, NULL, '123'), (3, NULL, '0456'), (4, 'B', '456'),
+                                                                                  (5, 'C', 'abc'), (6, NULL, 'x9'), (7, 'D', '789'), (8, NULL, '0999'), (9, 'E', '999');
+                            INSERT INTO link (link_id, to_page_id, from_page_id, length, redirect_page_id) VALUES (1, 7, 1, 1, NULL), (2, 7, 2, 1, NULL), (3, 1, 2, 1, NULL),
+                                                                                                                  (4, 2, 7, 1, NULL), (5, 7, 3, 1, 2), (6, 4, 9, 1, NULL);
+                            INSERT INTO redirect (page_id, target_page_id, title) VALUES (2, 7, 'A2'), (5, 4, 'C'), (9, 8, 'E');
+                            INSERT INTO frontier (page_id, state, depth, attempts) VALUES (1, 'done', 0, 1), (2, 'queued', 1, 0), (3, 'queued', 1, 0), (4, 'done', 1, 1),
+                                                                                          (5, 'done', 0, 1), (6, 'queued', 1, 0), (7, 'queued', 1, 0), (9, 'done', 2, 1);")
+            .unwrap();
+
+        make_ext_page_ids_integers(&conn);
+
+        assert_eq!(rows::<String>(&conn, "SELECT page_id || ' ' || IFNULL(title, '-') || ' ' || IFNULL(ext_page_id, '-') || ' ' || typeof(ext_page_id) FROM page ORDER BY page_id"),
+                   vec!["1 A 123 integer", "3 B 456 integer", "5 C - null", "6 - - null", "7 D 789 integer", "8 E 999 integer"]);
+        // Links between merged pages that end up on one page, or twice between
+        // the same two, are dropped.
+        assert_eq!(rows::<String>(&conn, "SELECT to_page_id || ' ' || from_page_id || ' ' || IFNULL(redirect_page_id, '-') FROM link ORDER BY link_id"),
+                   vec!["7 1 -", "1 7 -", "7 3 1", "3 8 -"]);
+        assert_eq!(rows::<String>(&conn, "SELECT page_id || ' ' || target_page_id || ' ' || title FROM redirect ORDER BY page_id"),
+                   vec!["1 7 A2", "5 3 C"]);
+        assert_eq!(rows::<String>(&conn, "SELECT page_id || ' ' || state || ' ' || depth FROM frontier ORDER BY page_id"),
+                   vec!["1 done 0", "3 queued 1", "5 done 0", "6 failed 1", "7 queued 1", "8 done 2"]);
+    }
+
+    #[test]
+    fn refuses_databases_from_a_newer_build() {
+        let mut conn = database_at(MIGRATIONS.len(), false);
+        conn.execute("UPDATE schema_version SET version = ?1", &[&(SCHEMA_VERSION + 1)]).unwrap();
+        let newer_schema = schema(&conn);
+
+        match migrate(&mut conn) {
+            Err(WikiRacerError::SchemaTooNew(version, known_version)) => assert_eq!((version, known_version), (SCHEMA_VERSION + 1, SCHEMA_VERSION)),
+            other => panic!("Expected SchemaTooNew, got {:?}", other),
+        }
+        assert_eq!(schema_version(&conn), SCHEMA_VERSION + 1);
+        assert_eq!(schema(&conn), newer_schema);
+    }
+}
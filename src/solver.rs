@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use store::GraphView;
+
+#[derive(Debug, PartialEq)]
+pub enum PathResult {
+    Found(Vec<PathStep>),
+    NoPath,
+    Unknown(String),
+}
+
+// A page on a path, along with the redirect a racer clicks to get there when
+// the link on the previous page does not name it directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathStep {
+    pub title: String,
+    pub redirect_opt: Option<String>,
+}
+
+impl PathStep {
+    pub fn new(title: String) -> PathStep {
+        PathStep {
+            title,
+            redirect_opt: None,
+        }
+    }
+}
+
+impl fmt::Display for PathStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.redirect_opt {
+            Some(ref redirect) => write!(f, "{} (redirect) -> {}", redirect, self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+pub fn find_path<G: GraphView>(graph: &G, from: &str, to: &str) -> PathResult {
+    let from_page_id = match graph.find_crawled_page(from) {
+        Some(page_id) => page_id,
+        None => return PathResult::Unknown(from.to_string()),
+    };
+    let to_page_id = match graph.find_crawled_page(to) {
+        Some(page_id) => page_id,
+        None => return PathResult::Unknown(to.to_string()),
+    };
+
+    match bfs(graph, from_page_id, to_page_id) {
+        Some(steps) => {
+            PathResult::Found(steps.into_iter()
+                .map(|(page_id, redirect_page_id_opt)| {
+                    PathStep {
+                        title: graph.page_label(page_id),
+                        redirect_opt: redirect_page_id_opt.map(|redirect_page_id| graph.redirect_title(redirect_page_id)),
+                    }
+                })
+                .collect())
+        }
+        None => PathResult::NoPath,
+    }
+}
+
+// Returns the pages of the path, each with the redirect its link went through.
+// Searches a level at a time from whichever end has fewer pages waiting, and
+// only asks for the links of a page once it is reached.
+fn bfs<G: GraphView>(graph: &G, from_page_id: i64, to_page_id: i64) -> Option<Vec<(i64, Option<i64>)>> {
+    // Each page reached from `from` with the page before it, and each page
+    // reached from `to` with the page after it, along with the redirect of
+    // the link between the two.
+    let mut forward_parents: HashMap<i64, (i64, Option<i64>)> = HashMap::new();
+    let mut backward_parents: HashMap<i64, (i64, Option<i64>)> = HashMap::new();
+    forward_parents.insert(from_page_id, (from_page_id, None));
+    backward_parents.insert(to_page_id, (to_page_id, None));
+
+    let mut forward_level = vec![from_page_id];
+    let mut backward_level = vec![to_page_id];
+
+    let mut meeting_opt = if from_page_id == to_page_id { Some(from_page_id) } else { None };
+
+    while meeting_opt.is_none() && !forward_level.is_empty() && !backward_level.is_empty() {
+        meeting_opt = if forward_level.len() <= backward_level.len() {
+            expand_level(&mut forward_level, &mut forward_parents, &backward_parents, |page_id| graph.links_from(page_id))
+        } else {
+            expand_level(&mut backward_level, &mut backward_parents, &forward_parents, |page_id| graph.links_to(page_id))
+        };
+    }
+
+    meeting_opt.map(|meeting_page_id| {
+        let mut path = vec![];
+        let mut current = meeting_page_id;
+        while current != from_page_id {
+            let (parent, redirect_page_id_opt) = forward_parents[&current];
+            path.push((current, redirect_page_id_opt));
+            current = parent;
+        }
+        path.push((from_page_id, None));
+        path.reverse();
+
+        let mut current = meeting_page_id;
+        while current != to_page_id {
+            let (child, redirect_page_id_opt) = backward_parents[&current];
+            path.push((child, redirect_page_id_opt));
+            current = child;
+        }
+        path
+    })
+}
+
+// Moves one side a level further, stopping at the first page the other side
+// has reached. Any page met this way lies on a shortest path, since one met a
+// level earlier would have been found then.
+fn expand_level<F>(level: &mut Vec<i64>, parents: &mut HashMap<i64, (i64, Option<i64>)>, other_parents: &HashMap<i64, (i64, Option<i64>)>, neighbors: F) -> Option<i64>
+    where F: Fn(i64) -> Vec<(i64, Option<i64>)>
+{
+    let mut next_level = vec![];
+
+    for &page_id in level.iter() {
+        for (neighbor, redirect_page_id_opt) in neighbors(page_id) {
+            if let Entry::Vacant(entry) = parents.entry(neighbor) {
+                entry.insert((page_id, redirect_page_id_opt));
+                if other_parents.contains_key(&neighbor) {
+                    return Some(neighbor);
+                }
+                next_level.push(neighbor);
+            }
+        }
+    }
+
+    *level = next_level;
+    None
+}
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use utils::WikiRacerError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Number(String),
+    Text(String),
+}
+
+impl SqlValue {
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            SqlValue::Number(ref number) => number.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            SqlValue::Text(ref text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+// Streams the rows of one table out of a mysqldump file, such as the
+// `*.sql.gz` files of a Wikipedia dump. Other statements are skipped, and
+// only one `INSERT` line is held in memory at a time.
+pub struct SqlDumpReader<R: BufRead> {
+    input: R,
+    prefix: Vec<u8>,
+    line: Vec<u8>,
+    rows: VecDeque<Vec<SqlValue>>,
+}
+
+impl<R: BufRead> SqlDumpReader<R> {
+    pub fn new(input: R, table: &str) -> SqlDumpReader<R> {
+        SqlDumpReader {
+            input,
+            prefix: format!("INSERT INTO `{}` VALUES ", table).into_bytes(),
+            line: vec![],
+            rows: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SqlDumpReader<R> {
+    type Item = Result<Vec<SqlValue>, WikiRacerError>;
+
+    fn next(&mut self) -> Option<Result<Vec<SqlValue>, WikiRacerError>> {
+        while self.rows.is_empty() {
+            self.line.clear();
+            match self.input.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(err) => return Some(Err(WikiRacerError::IoError(err.to_string()))),
+            }
+
+            if self.line.starts_with(&self.prefix) {
+                if let Err(err) = parse_tuples(&self.line[self.prefix.len()..], &mut self.rows) {
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        self.rows.pop_front().map(Ok)
+    }
+}
+
+// Parses `(..),(..),..;` into rows.
+fn parse_tuples(bytes: &[u8], rows: &mut VecDeque<Vec<SqlValue>>) -> Result<(), WikiRacerError> {
+    let mut pos = 0;
+
+    loop {
+        if bytes.get(pos) != Some(&b'(') {
+            return Err(WikiRacerError::FormatError("Dump row did not start with ("));
+        }
+        pos += 1;
+
+        let mut row = vec![];
+        loop {
+            let (value, next_pos) = parse_value(bytes, pos)?;
+            row.push(value);
+            pos = next_pos;
+
+            match bytes.get(pos) {
+                Some(&b',') => pos += 1,
+                Some(&b')') => {
+                    pos += 1;
+                    break;
+                }
+                _ => return Err(WikiRacerError::FormatError("Dump value was not followed by , or )")),
+            }
+        }
+        rows.push_back(row);
+
+        match bytes.get(pos) {
+            Some(&b',') => pos += 1,
+            Some(&b';') => return Ok(()),
+            _ => return Err(WikiRacerError::FormatError("Dump row was not followed by , or ;")),
+        }
+    }
+}
+
+fn parse_value(bytes: &[u8], start: usize) -> Result<(SqlValue, usize), WikiRacerError> {
+    if bytes.get(start) == Some(&b'\'') {
+        let mut text = vec![];
+        let mut pos = start + 1;
+        loop {
+            match bytes.get(pos) {
+                Some(&b'\\') => {
+                    let escaped = match bytes.get(pos + 1) {
+                        Some(&b'0') => 0,
+                        Some(&b'b') => 8,
+                        Some(&b'n') => b'\n',
+                        Some(&b'r') => b'\r',
+                        Some(&b't') => b'\t',
+                        Some(&b'Z') => 26,
+                        Some(&byte) => byte,
+                        None => return Err(WikiRacerError::FormatError("Dump string ended in an escape")),
+                    };
+                    text.push(escaped);
+                    pos += 2;
+                }
+                Some(&b'\'') => return Ok((SqlValue::Text(String::from_utf8_lossy(&text).into_owned()), pos + 1)),
+                Some(&byte) => {
+                    text.push(byte);
+                    pos += 1;
+                }
+                None => return Err(WikiRacerError::FormatError("Dump string was not closed")),
+            }
+        }
+    }
+
+    let mut pos = start;
+    while pos < bytes.len() && bytes[pos] != b',' && bytes[pos] != b')' {
+        pos += 1;
+    }
+
+    let token = String::from_utf8_lossy(&bytes[start..pos]).into_owned();
+    if token == "NULL" {
+        Ok((SqlValue::Null, pos))
+    } else if token.is_empty() {
+        Err(WikiRacerError::FormatError("Dump value was empty"))
+    } else {
+        Ok((SqlValue::Number(token), pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_rows(dump: &str, table: &str) -> Vec<Vec<SqlValue>> {
+        SqlDumpReader::new(dump.as_bytes(), table).map(|row_result| row_result.unwrap()).collect()
+    }
+
+    fn number(number: &str) -> SqlValue {
+        SqlValue::Number(number.to_string())
+    }
+
+    fn text(text: &str) -> SqlValue {
+        SqlValue::Text(text.to_string())
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let cases = [(r"'plain'", "plain"),
+                      (r"'O\'Brien'", "O'Brien"),
+                      (r"'C:\\Windows'", r"C:\Windows"),
+                      (r#"'\"Quoted\"'"#, "\"Quoted\""),
+                      (r"'a\nb\tc\rd'", "a\nb\tc\rd"),
+                      (r"'nul\0'", "nul\0"),
+                      (r"'Gödel,_(logic)'", "Gödel,_(logic)"),
+                      (r"''", "")];
+
+        for &(value, expected) in &cases {
+            let dump = format!("INSERT INTO `page` VALUES ({});\n", value);
+            assert_eq!(read_rows(&dump, "page"), vec![vec![text(expected)]], "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_null_and_numbers() {
+        let dump = "INSERT INTO `redirect` VALUES (2,0,'Albert_Einstein',NULL,''),(7,-1,'',NULL,NULL),(8,0,'0.5',1.5,NULL);\n";
+
+        assert_eq!(read_rows(dump, "redirect"),
+                   vec![vec![number("2"), number("0"), text("Albert_Einstein"), SqlValue::Null, text("")],
+                        vec![number("7"), number("-1"), text(""), SqlValue::Null, SqlValue::Null],
+                        vec![number("8"), number("0"), text("0.5"), number("1.5"), SqlValue::Null]]);
+        assert_eq!(SqlValue::Null.as_i64(), None);
+        assert_eq!(number("-1").as_i64(), Some(-1));
+        assert_eq!(number("1.5").as_i64(), None);
+        assert_eq!(text("2").as_i64(), None);
+    }
+
+    #[test]
+    fn reads_multi_row_inserts_of_the_table_only() {
+        let dump = "-- MySQL dump 10.16\n\
+                    DROP TABLE IF EXISTS `pagelinks`;\n\
+                    CREATE TABLE `pagelinks` (\n  `pl_from` int(8) unsigned NOT NULL DEFAULT '0'\n);\n\
+                    INSERT INTO `linktarget` VALUES (1,0,'Elsewhere');\n\
+                    INSERT INTO `pagelinks` VALUES (1,0,'A_(letter)',0),(1,0,'B,C',0);\n\
+                    INSERT INTO `pagelinks_old` VALUES (9,0,'Never',0);\n\
+                    INSERT INTO `pagelinks` VALUES (2,0,'C',0);\n";
+
+        let rows = read_rows(dump, "pagelinks");
+        assert_eq!(rows.iter().map(|row| (row[0].as_i64(), row[2].as_str())).collect::<Vec<_>>(),
+                   vec![(Some(1), Some("A_(letter)")), (Some(1), Some("B,C")), (Some(2), Some("C"))]);
+    }
+
+    #[test]
+    fn parses_both_pagelinks_formats() {
+        // pl_from, pl_namespace, pl_title, pl_from_namespace
+        let old = "INSERT INTO `pagelinks` VALUES (12,0,'Albert_Einstein',0),(12,14,'Physicists',0);\n";
+        // pl_from, pl_from_namespace, pl_target_id
+        let new = "INSERT INTO `pagelinks` VALUES (12,0,30),(12,0,31);\n";
+
+        assert_eq!(read_rows(old, "pagelinks"),
+                   vec![vec![number("12"), number("0"), text("Albert_Einstein"), number("0")],
+                        vec![number("12"), number("14"), text("Physicists"), number("0")]]);
+        assert_eq!(read_rows(new, "pagelinks"),
+                   vec![vec![number("12"), number("0"), number("30")], vec![number("12"), number("0"), number("31")]]);
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        for dump in &["INSERT INTO `page` VALUES 1,2;\n",
+                      "INSERT INTO `page` VALUES (1,2)\n",
+                      "INSERT INTO `page` VALUES (1,,2);\n",
+                      "INSERT INTO `page` VALUES (1,'open);\n",
+                      // mysqldump escapes a quote with a backslash, never by doubling it.
+                      "INSERT INTO `page` VALUES (1,'It''s');\n",
+                      "INSERT INTO `page` VALUES (1,'escape\\"] {
+            match SqlDumpReader::new(dump.as_bytes(), "page").next() {
+                Some(Err(WikiRacerError::FormatError(_))) => {}
+                other => panic!("Expected a format error for {:?}, got {:?}", dump, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use flate2::read::MultiGzDecoder;
+use frontier::DONE;
+use rusqlite::{Connection, Transaction};
+use sql_dump::{SqlDumpReader, SqlValue};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use utils::WikiRacerError;
+
+const ARTICLE_NAMESPACE: i64 = 0;
+
+const PROGRESS_INTERVAL: usize = 1000000;
+
+#[derive(Debug, Default)]
+pub struct SqlImportStats {
+    pub pages: usize,
+    pub redirects: usize,
+    pub links: usize,
+}
+
+// Fills the database from the `page`, `redirect`, `pagelinks` and, for newer
+// dumps, `linktarget` tables of a Wikipedia dump. Rows go straight into the
+// tables rather than through the writer, a full dump is billions of links.
+pub fn import_sql_dumps(conn: &mut Connection, dir: &Path) -> Result<SqlImportStats, WikiRacerError> {
+    let mut stats = SqlImportStats::default();
+
+    let transaction = conn.transaction().unwrap();
+    let (page_ids, page_ids_by_title) = import_pages(&transaction, open_dump(dir, "page")?, &mut stats)?;
+    transaction.commit().unwrap();
+    println!("Imported {} pages", stats.pages);
+
+    let transaction = conn.transaction().unwrap();
+    let redirects = import_redirects(&transaction, open_dump(dir, "redirect")?, &page_ids, &page_ids_by_title, &mut stats)?;
+    transaction.commit().unwrap();
+    println!("Imported {} redirects", stats.redirects);
+
+    let link_targets_opt = match find_dump(dir, "linktarget") {
+        Some(path) => Some(read_link_targets(open_dump_file(&path)?, &page_ids_by_title)?),
+        None => None,
+    };
+
+    let transaction = conn.transaction().unwrap();
+    import_links(&transaction, open_dump(dir, "pagelinks")?, &page_ids, &page_ids_by_title, &redirects, link_targets_opt.as_ref(), &mut stats)?;
+    transaction.commit().unwrap();
+    println!("Imported {} links", stats.links);
+
+    Ok(stats)
+}
+
+// Page ids by ext page id and by title.
+type PageIds = (HashMap<i64, i64>, HashMap<String, i64>);
+
+// Returns the page ids of articles only.
+fn import_pages(trans: &Transaction, input: Box<dyn BufRead>, stats: &mut SqlImportStats) -> Result<PageIds, WikiRacerError> {
+    let mut page_ids = load_page_ids(trans);
+    let mut page_ids_by_title = HashMap::new();
+
+    let mut insert_page_stmt = trans.prepare("INSERT INTO page (title, ext_page_id) VALUES (?1, ?2)").unwrap();
+    let mut update_title_stmt = trans.prepare("UPDATE page SET title = ?1 WHERE page_id = ?2").unwrap();
+    let mut insert_frontier_stmt = trans.prepare("INSERT OR IGNORE INTO frontier (page_id, state, depth, attempts) VALUES (?1, ?2, 0, 0)").unwrap();
+    let mut update_frontier_stmt = trans.prepare("UPDATE frontier SET state = ?1 WHERE page_id = ?2").unwrap();
+
+    for row_result in SqlDumpReader::new(input, "page") {
+        let row = row_result?;
+        let (ext_page_id, title) = match (get_i64(&row, 0), get_i64(&row, 1), get_title(&row, 2)) {
+            (Some(ext_page_id), Some(ARTICLE_NAMESPACE), Some(title)) => (ext_page_id, title),
+            _ => continue,
+        };
+
+        let page_id = match page_ids.get(&ext_page_id).cloned() {
+            Some(page_id) => {
+                update_title_stmt.execute(&[&title, &page_id]).unwrap();
+                page_id
+            }
+            None => {
+                insert_page_stmt.execute(&[&title, &ext_page_id]).unwrap();
+                trans.last_insert_rowid()
+            }
+        };
+        insert_frontier_stmt.execute(&[&page_id, &DONE]).unwrap();
+        update_frontier_stmt.execute(&[&DONE, &page_id]).unwrap();
+
+        page_ids.insert(ext_page_id, page_id);
+        page_ids_by_title.insert(title, page_id);
+
+        stats.pages += 1;
+        if stats.pages.is_multiple_of(PROGRESS_INTERVAL) {
+            println!("{} pages", stats.pages);
+        }
+    }
+
+    Ok((page_ids, page_ids_by_title))
+}
+
+// Returns the target page id by redirect page id.
+fn import_redirects(trans: &Transaction,
+                    input: Box<dyn BufRead>,
+                    page_ids: &HashMap<i64, i64>,
+                    page_ids_by_title: &HashMap<String, i64>,
+                    stats: &mut SqlImportStats)
+                    -> Result<HashMap<i64, i64>, WikiRacerError> {
+    let mut redirects = HashMap::new();
+
+    let mut insert_redirect_stmt = trans.prepare("INSERT OR REPLACE INTO redirect (page_id, target_page_id, title) SELECT ?1, ?2, title FROM page WHERE page_id = ?1")
+        .unwrap();
+
+    for row_result in SqlDumpReader::new(input, "redirect") {
+        let row = row_result?;
+        // Interwiki redirects lead off the wiki.
+        let interwiki = row.get(3).and_then(SqlValue::as_str).unwrap_or("");
+        let (ext_page_id, target_title) = match (get_i64(&row, 0), get_i64(&row, 1), get_title(&row, 2)) {
+            (Some(ext_page_id), Some(ARTICLE_NAMESPACE), Some(target_title)) if interwiki.is_empty() => (ext_page_id, target_title),
+            _ => continue,
+        };
+
+        if let (Some(&page_id), Some(&target_page_id)) = (page_ids.get(&ext_page_id), page_ids_by_title.get(&target_title)) {
+            if page_id != target_page_id {
+                insert_redirect_stmt.execute(&[&page_id, &target_page_id]).unwrap();
+                redirects.insert(page_id, target_page_id);
+                stats.redirects += 1;
+            }
+        }
+    }
+
+    Ok(redirects)
+}
+
+// Returns the page id by link target id, of link targets that are articles.
+fn read_link_targets(input: Box<dyn BufRead>, page_ids_by_title: &HashMap<String, i64>) -> Result<HashMap<i64, i64>, WikiRacerError> {
+    let mut link_targets = HashMap::new();
+
+    for row_result in SqlDumpReader::new(input, "linktarget") {
+        let row = row_result?;
+        if let (Some(link_target_id), Some(ARTICLE_NAMESPACE), Some(title)) = (get_i64(&row, 0), get_i64(&row, 1), get_title(&row, 2)) {
+            if let Some(&page_id) = page_ids_by_title.get(&title) {
+                link_targets.insert(link_target_id, page_id);
+            }
+        }
+    }
+
+    Ok(link_targets)
+}
+
+// Older dumps name the target of a link by (pl_namespace, pl_title), newer
+// ones by a pl_target_id into linktarget. pagelinks is ordered by pl_from, so
+// duplicates only have to be looked for among the links of the current page.
+fn import_links(trans: &Transaction,
+                input: Box<dyn BufRead>,
+                page_ids: &HashMap<i64, i64>,
+                page_ids_by_title: &HashMap<String, i64>,
+                redirects: &HashMap<i64, i64>,
+                link_targets_opt: Option<&HashMap<i64, i64>>,
+                stats: &mut SqlImportStats)
+                -> Result<(), WikiRacerError> {
+    let existing_links = load_links(trans);
+
+    let mut insert_link_stmt = trans.prepare("INSERT INTO link (to_page_id, from_page_id, length, redirect_page_id) VALUES (?1, ?2, 1, ?3)").unwrap();
+
+    let mut current_from_page_id = None;
+    let mut current_to_page_ids = HashSet::new();
+
+    for row_result in SqlDumpReader::new(input, "pagelinks") {
+        let row = row_result?;
+
+        let (ext_from_page_id, from_namespace_opt, linked_page_id_opt) = if row.len() >= 4 {
+            let linked_page_id_opt = match (get_i64(&row, 1), get_title(&row, 2)) {
+                (Some(ARTICLE_NAMESPACE), Some(title)) => page_ids_by_title.get(&title).cloned(),
+                _ => None,
+            };
+            (get_i64(&row, 0), get_i64(&row, 3), linked_page_id_opt)
+        } else {
+            let link_targets = match link_targets_opt {
+                Some(link_targets) => link_targets,
+                None => return Err(WikiRacerError::IoError("pagelinks refers to link targets but there is no linktarget dump".to_string())),
+            };
+            (get_i64(&row, 0), get_i64(&row, 1), get_i64(&row, 2).and_then(|link_target_id| link_targets.get(&link_target_id).cloned()))
+        };
+
+        let (from_page_id, linked_page_id) = match (ext_from_page_id.and_then(|ext_page_id| page_ids.get(&ext_page_id)), from_namespace_opt, linked_page_id_opt) {
+            (Some(&from_page_id), Some(ARTICLE_NAMESPACE), Some(linked_page_id)) => (from_page_id, linked_page_id),
+            _ => continue,
+        };
+
+        let (to_page_id, redirect_page_id_opt) = match redirects.get(&linked_page_id) {
+            Some(&target_page_id) => (target_page_id, Some(linked_page_id)),
+            None => (linked_page_id, None),
+        };
+
+        if current_from_page_id != Some(from_page_id) {
+            current_from_page_id = Some(from_page_id);
+            current_to_page_ids.clear();
+        }
+
+        if to_page_id == from_page_id || !current_to_page_ids.insert(to_page_id) || existing_links.contains(&(to_page_id, from_page_id)) {
+            continue;
+        }
+
+        insert_link_stmt.execute(&[&to_page_id, &from_page_id, &redirect_page_id_opt]).unwrap();
+
+        stats.links += 1;
+        if stats.links.is_multiple_of(PROGRESS_INTERVAL) {
+            println!("{} links", stats.links);
+        }
+    }
+
+    Ok(())
+}
+
+fn load_page_ids(conn: &Connection) -> HashMap<i64, i64> {
+    let mut stmt = conn.prepare("SELECT page_id, ext_page_id FROM page").unwrap();
+
+    let page_iter = stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap();
+
+    let mut page_ids = HashMap::new();
+
+    for page_result in page_iter {
+        let (page_id, ext_page_id_opt): (i64, Option<i64>) = page_result.unwrap();
+        if let Some(ext_page_id) = ext_page_id_opt {
+            page_ids.insert(ext_page_id, page_id);
+        }
+    }
+
+    page_ids
+}
+
+fn load_links(conn: &Connection) -> HashSet<(i64, i64)> {
+    let mut stmt = conn.prepare("SELECT to_page_id, from_page_id FROM link").unwrap();
+
+    let link_iter = stmt.query_map(&[], |row| (row.get(0), row.get(1))).unwrap();
+
+    link_iter.map(|link_result| link_result.unwrap()).collect()
+}
+
+fn get_i64(row: &[SqlValue], index: usize) -> Option<i64> {
+    row.get(index).and_then(SqlValue::as_i64)
+}
+
+// Dumps store titles with underscores, the API and the page table use spaces.
+fn get_title(row: &[SqlValue], index: usize) -> Option<String> {
+    row.get(index).and_then(SqlValue::as_str).map(|title| title.replace('_', " "))
+}
+
+// Accepts both the bare name and the dated one of a downloaded dump, such as
+// `enwiki-20170401-page.sql.gz`, compressed or not.
+fn find_dump(dir: &Path, table: &str) -> Option<PathBuf> {
+    let names: Vec<String> = vec![format!("{}.sql.gz", table), format!("{}.sql", table)];
+
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if names.iter().any(|name| file_name == *name || file_name.ends_with(&format!("-{}", name))) {
+            return Some(entry.path());
+        }
+    }
+    None
+}
+
+fn open_dump(dir: &Path, table: &str) -> Result<Box<dyn BufRead>, WikiRacerError> {
+    match find_dump(dir, table) {
+        Some(path) => open_dump_file(&path),
+        None => Err(WikiRacerError::IoError(format!("No {}.sql.gz in {}", table, dir.display()))),
+    }
+}
+
+fn open_dump_file(path: &Path) -> Result<Box<dyn BufRead>, WikiRacerError> {
+    let file = File::open(path).map_err(|err| WikiRacerError::IoError(format!("{}: {}", path.display(), err)))?;
+
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+    use utils::open_or_create_database;
+
+    // page_id, page_namespace, page_title, page_restrictions, page_is_redirect
+    const PAGE: &str = "INSERT INTO `page` VALUES (1,0,'Albert_Einstein','',0),(2,0,'Einstein','',1),(3,0,'Physics','',0),(4,1,'Albert_Einstein','',0),(5,0,'O\\'Brien','',1);\n";
+
+    // rd_from, rd_namespace, rd_title, rd_interwiki, rd_fragment
+    const REDIRECT: &str = "INSERT INTO `redirect` VALUES (2,0,'Albert_Einstein','',NULL),(5,0,'Brian_O\\'Brien','fr','');\n";
+
+    // pl_from, pl_namespace, pl_title, pl_from_namespace
+    const OLD_PAGELINKS: &str = "INSERT INTO `pagelinks` VALUES (1,0,'Nowhere',0),(1,1,'Albert_Einstein',0),(3,0,'Einstein',0),(3,0,'Physics',0),(3,0,'Albert_Einstein',0),\
+                                         (4,0,'Physics',1),(5,0,'Physics',0);\n";
+
+    // lt_id, lt_namespace, lt_title
+    const LINKTARGET: &str = "INSERT INTO `linktarget` VALUES (10,0,'Einstein'),(11,0,'Physics'),(12,0,'Albert_Einstein'),(13,1,'Albert_Einstein'),(14,0,'Nowhere');\n";
+
+    // pl_from, pl_from_namespace, pl_target_id
+    const NEW_PAGELINKS: &str = "INSERT INTO `pagelinks` VALUES (1,0,14),(1,0,13),(3,0,10),(3,0,11),(3,0,12),(4,1,11),(5,0,11);\n";
+
+    // Writes the dumps, gzipped and named as downloaded, into a fresh directory.
+    fn write_dumps(name: &str, dumps: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("wikiracer-sql-import-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for &(table, dump) in dumps {
+            let file = File::create(dir.join(format!("enwiki-20170401-{}.sql.gz", table))).unwrap();
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(dump.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        dir
+    }
+
+    fn import(name: &str, dumps: &[(&str, &str)]) -> Result<(Connection, SqlImportStats), WikiRacerError> {
+        let dir = write_dumps(name, dumps);
+        let mut conn = open_or_create_database(":memory:").unwrap();
+        let stats_result = import_sql_dumps(&mut conn, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+        stats_result.map(|stats| (conn, stats))
+    }
+
+    // Everything by ext page id, so the rows read the same whichever page ids were handed out.
+    fn check_rows(conn: &Connection, stats: &SqlImportStats) {
+        assert_eq!((stats.pages, stats.redirects, stats.links), (4, 1, 2));
+
+        let mut stmt = conn.prepare("SELECT page.ext_page_id, page.title, frontier.state FROM page JOIN frontier ON frontier.page_id = page.page_id ORDER BY page.ext_page_id").unwrap();
+        let pages: Vec<(i64, String, String)> = stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap().map(|page| page.unwrap()).collect();
+        assert_eq!(pages,
+                   vec![(1, "Albert Einstein".to_string(), DONE.to_string()),
+                        (2, "Einstein".to_string(), DONE.to_string()),
+                        (3, "Physics".to_string(), DONE.to_string()),
+                        (5, "O'Brien".to_string(), DONE.to_string())]);
+
+        let redirect: (i64, i64, String) = conn.query_row("SELECT page.ext_page_id, target_page.ext_page_id, redirect.title FROM redirect
+                                                           JOIN page ON page.page_id = redirect.page_id
+                                                           JOIN page AS target_page ON target_page.page_id = redirect.target_page_id",
+                       &[],
+                       |row| (row.get(0), row.get(1), row.get(2)))
+            .unwrap();
+        assert_eq!(redirect, (2, 1, "Einstein".to_string()));
+
+        // The link through the redirect lands on its target, and the direct one to the same page is dropped.
+        let mut stmt = conn.prepare("SELECT to_page.ext_page_id, from_page.ext_page_id, redirect_page.ext_page_id FROM link
+                                     JOIN page AS to_page ON to_page.page_id = link.to_page_id
+                                     JOIN page AS from_page ON from_page.page_id = link.from_page_id
+                                     LEFT JOIN page AS redirect_page ON redirect_page.page_id = link.redirect_page_id
+                                     ORDER BY from_page.ext_page_id")
+            .unwrap();
+        let links: Vec<(i64, i64, Option<i64>)> = stmt.query_map(&[], |row| (row.get(0), row.get(1), row.get(2))).unwrap().map(|link| link.unwrap()).collect();
+        assert_eq!(links, vec![(1, 3, Some(2)), (3, 5, None)]);
+    }
+
+    #[test]
+    fn imports_pagelinks_by_title() {
+        let (conn, stats) = import("old", &[("page", PAGE), ("redirect", REDIRECT), ("pagelinks", OLD_PAGELINKS)]).unwrap();
+        check_rows(&conn, &stats);
+    }
+
+    #[test]
+    fn imports_pagelinks_by_link_target() {
+        let (conn, stats) = import("new", &[("page", PAGE), ("redirect", REDIRECT), ("linktarget", LINKTARGET), ("pagelinks", NEW_PAGELINKS)]).unwrap();
+        check_rows(&conn, &stats);
+    }
+
+    #[test]
+    fn needs_linktarget_for_pagelinks_by_link_target() {
+        match import("no-linktarget", &[("page", PAGE), ("redirect", REDIRECT), ("pagelinks", NEW_PAGELINKS)]) {
+            Err(WikiRacerError::IoError(ref message)) if message.contains("linktarget") => {}
+            other => panic!("Expected a missing linktarget dump, got {:?}", other.map(|(_, stats)| stats)),
+        }
+    }
+}
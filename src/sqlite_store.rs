@@ -0,0 +1,223 @@
+use frontier::{self, DONE};
+use rusqlite::Connection;
+use rusqlite::types::{FromSql, ToSql};
+use stats::{Stats, get_stats};
+use std::collections::HashMap;
+use store::{GraphStore, GraphView};
+
+// The graph in a database file, as laid out by the schema migrations.
+pub struct SqliteStore {
+    conn: Connection,
+    page_ids: PageIdCache,
+}
+
+impl SqliteStore {
+    // Takes a connection to a database already brought up to date.
+    pub fn new(conn: Connection) -> SqliteStore {
+        SqliteStore {
+            conn,
+            page_ids: PageIdCache::new(),
+        }
+    }
+}
+
+impl GraphView for SqliteStore {
+    // A page only counts as crawled once the writer has marked it done,
+    // otherwise its backlinks are not in the link table yet.
+    fn find_crawled_page(&self, title_or_ext_page_id: &str) -> Option<i64> {
+        let ext_page_id_opt: Option<i64> = title_or_ext_page_id.parse().ok();
+
+        query_optional(&self.conn,
+                       "SELECT redirect.target_page_id FROM redirect
+                        JOIN page ON page.page_id = redirect.page_id
+                        JOIN frontier ON frontier.page_id = redirect.target_page_id
+                        WHERE frontier.state = ?2 AND (redirect.title = ?1 OR page.ext_page_id = ?3)",
+                       &[&title_or_ext_page_id, &DONE, &ext_page_id_opt])
+            .or_else(|| {
+                query_optional(&self.conn,
+                               "SELECT page.page_id FROM page
+                                JOIN frontier ON frontier.page_id = page.page_id
+                                WHERE frontier.state = ?2 AND (page.title = ?1 OR page.ext_page_id = ?3)",
+                               &[&title_or_ext_page_id, &DONE, &ext_page_id_opt])
+            })
+    }
+
+    // Links point at the redirect target already, the redirect is only
+    // carried along to be shown.
+    fn links_from(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        let mut stmt = self.conn.prepare_cached("SELECT to_page_id, redirect_page_id FROM link WHERE from_page_id = ?1").unwrap();
+
+        let link_iter = stmt.query_map(&[&page_id], |row| (row.get(0), row.get(1))).unwrap();
+
+        link_iter.map(|link_result| link_result.unwrap()).collect()
+    }
+
+    fn links_to(&self, page_id: i64) -> Vec<(i64, Option<i64>)> {
+        let mut stmt = self.conn.prepare_cached("SELECT from_page_id, redirect_page_id FROM link WHERE to_page_id = ?1").unwrap();
+
+        let link_iter = stmt.query_map(&[&page_id], |row| (row.get(0), row.get(1))).unwrap();
+
+        link_iter.map(|link_result| link_result.unwrap()).collect()
+    }
+
+    fn page_label(&self, page_id: i64) -> String {
+        let (title_opt, ext_page_id_opt): (Option<String>, Option<i64>) = self.conn
+            .query_row("SELECT title, ext_page_id FROM page WHERE page_id = ?1", &[&page_id], |row| (row.get(0), row.get(1)))
+            .unwrap();
+
+        title_opt.or_else(|| ext_page_id_opt.map(|ext_page_id| ext_page_id.to_string())).unwrap_or_default()
+    }
+
+    fn redirect_title(&self, redirect_page_id: i64) -> String {
+        query_optional(&self.conn, "SELECT title FROM redirect WHERE page_id = ?1", &[&redirect_page_id]).unwrap_or_else(|| self.page_label(redirect_page_id))
+    }
+}
+
+impl GraphStore for SqliteStore {
+    // The bundled SQLite predates `ON CONFLICT DO UPDATE`, so the upsert is an
+    // insert that may do nothing followed by setting the title.
+    fn upsert_page(&mut self, title_opt: Option<String>, ext_page_id: i64) {
+        let page_id = self.page_ids.add(&self.conn, ext_page_id);
+
+        if let Some(title) = title_opt {
+            self.conn.prepare_cached("UPDATE page SET title = ?1 WHERE page_id = ?2").unwrap().execute(&[&title, &page_id]).unwrap();
+            frontier::mark_done(&self.conn, ext_page_id);
+        }
+    }
+
+    fn add_link(&mut self, to_ext_page_id: i64, from_ext_page_id: i64, position_opt: Option<i64>) {
+        let to_page_id_opt = self.page_ids.get(&self.conn, to_ext_page_id);
+        let from_page_id_opt = self.page_ids.get(&self.conn, from_ext_page_id);
+
+        if let (Some(to_page_id), Some(from_page_id)) = (to_page_id_opt, from_page_id_opt) {
+            let (to_page_id, redirect_page_id_opt) = match query_optional(&self.conn, "SELECT target_page_id FROM redirect WHERE page_id = ?1", &[&to_page_id]) {
+                Some(target_page_id) => (target_page_id, Some(to_page_id)),
+                None => (to_page_id, None),
+            };
+            // The unique index on the ends of a link makes this an insert
+            // that may do nothing.
+            if to_page_id != from_page_id {
+                self.conn
+                    .prepare_cached("INSERT OR IGNORE INTO link (to_page_id, from_page_id, length, redirect_page_id, position) VALUES (?1, ?2, 1, ?3, ?4)")
+                    .unwrap()
+                    .execute(&[&to_page_id, &from_page_id, &redirect_page_id_opt, &position_opt])
+                    .unwrap();
+            }
+        }
+    }
+
+    fn add_redirect(&mut self, ext_page_id: i64, title: String, target_ext_page_id: i64) {
+        let page_id_opt = self.page_ids.get(&self.conn, ext_page_id);
+        let target_page_id_opt = self.page_ids.get(&self.conn, target_ext_page_id);
+
+        if let (Some(page_id), Some(target_page_id)) = (page_id_opt, target_page_id_opt) {
+            if page_id != target_page_id {
+                self.conn
+                    .prepare_cached("INSERT OR REPLACE INTO redirect (page_id, target_page_id, title) VALUES (?1, ?2, ?3)")
+                    .unwrap()
+                    .execute(&[&page_id, &target_page_id, &title])
+                    .unwrap();
+            }
+        }
+    }
+
+    fn redirect_target(&self, ext_page_id: i64) -> Option<i64> {
+        query_optional(&self.conn,
+                       "SELECT target_page.ext_page_id FROM redirect
+                        JOIN page ON page.page_id = redirect.page_id
+                        JOIN page AS target_page ON target_page.page_id = redirect.target_page_id
+                        WHERE page.ext_page_id = ?1 AND target_page.ext_page_id IS NOT NULL",
+                       &[&ext_page_id])
+    }
+
+    fn queue_seed(&mut self, ext_page_id: i64) {
+        frontier::queue_seed(&mut self.conn, ext_page_id);
+    }
+
+    fn queue_discovered(&mut self, crawled_ext_page_id: i64, discovered_ext_page_id: i64) {
+        frontier::queue_discovered(&self.conn, crawled_ext_page_id, discovered_ext_page_id);
+    }
+
+    fn take_queued(&mut self, max_in_flight: usize) -> Vec<i64> {
+        frontier::take_queued(&mut self.conn, max_in_flight)
+    }
+
+    fn reclaim_in_flight(&mut self) {
+        frontier::reclaim_in_flight(&self.conn);
+    }
+
+    fn requeue_or_fail(&mut self, ext_page_id: i64) {
+        frontier::requeue_or_fail(&self.conn, ext_page_id);
+    }
+
+    fn mark_failed(&mut self, ext_page_id: i64) {
+        frontier::mark_failed(&self.conn, ext_page_id);
+    }
+
+    fn stats(&self) -> Stats {
+        get_stats(&self.conn)
+    }
+
+    // A transaction would borrow the connection the writes go through, so
+    // it is begun and committed by hand.
+    fn atomically<F: FnOnce(&mut SqliteStore)>(&mut self, write: F) {
+        self.conn.execute_batch("BEGIN").unwrap();
+        write(self);
+        self.conn.execute_batch("COMMIT").unwrap();
+    }
+}
+
+// Every link looks up the pages at both of its ends, mostly the same few
+// crawled pages over and over, so ids already seen are kept. Pages are never
+// deleted, so a remembered id stays right.
+const PAGE_ID_CACHE_CAPACITY: usize = 1000000;
+
+struct PageIdCache {
+    page_ids: HashMap<i64, i64>,
+}
+
+impl PageIdCache {
+    fn new() -> PageIdCache {
+        PageIdCache { page_ids: HashMap::new() }
+    }
+
+    fn get(&mut self, conn: &Connection, ext_page_id: i64) -> Option<i64> {
+        if let Some(&page_id) = self.page_ids.get(&ext_page_id) {
+            return Some(page_id);
+        }
+
+        let page_id_opt = query_optional(conn, "SELECT page_id FROM page WHERE ext_page_id = ?1", &[&ext_page_id]);
+        if let Some(page_id) = page_id_opt {
+            self.remember(ext_page_id, page_id);
+        }
+        page_id_opt
+    }
+
+    fn add(&mut self, conn: &Connection, ext_page_id: i64) -> i64 {
+        if let Some(&page_id) = self.page_ids.get(&ext_page_id) {
+            return page_id;
+        }
+
+        let inserted = conn.prepare_cached("INSERT OR IGNORE INTO page (ext_page_id) VALUES (?1)").unwrap().execute(&[&ext_page_id]).unwrap();
+        if inserted > 0 {
+            let page_id = conn.last_insert_rowid();
+            self.remember(ext_page_id, page_id);
+            page_id
+        } else {
+            self.get(conn, ext_page_id).unwrap()
+        }
+    }
+
+    fn remember(&mut self, ext_page_id: i64, page_id: i64) {
+        if self.page_ids.len() >= PAGE_ID_CACHE_CAPACITY {
+            self.page_ids.clear();
+        }
+        self.page_ids.insert(ext_page_id, page_id);
+    }
+}
+
+fn query_optional<T: FromSql>(conn: &Connection, sql: &str, params: &[&dyn ToSql]) -> Option<T> {
+    let mut stmt = conn.prepare_cached(sql).unwrap();
+    let mut value_iter = stmt.query_map(params, |row| row.get(0)).unwrap();
+    value_iter.next().map(|value_result| value_result.unwrap())
+}
@@ -0,0 +1,46 @@
+use frontier::{DONE, FAILED, IN_FLIGHT, QUEUED};
+use rusqlite::Connection;
+
+#[derive(Debug)]
+pub struct Stats {
+    pub pages: i64,
+    pub crawled_pages: i64,
+    pub links: i64,
+    pub redirects: i64,
+    pub queued: i64,
+    pub in_flight: i64,
+    pub done: i64,
+    pub failed: i64,
+    pub max_depth: i64,
+}
+
+pub fn get_stats(conn: &Connection) -> Stats {
+    Stats {
+        pages: count(conn, "SELECT COUNT(*) FROM page"),
+        crawled_pages: count_state(conn, DONE),
+        links: count(conn, "SELECT COUNT(*) FROM link"),
+        redirects: count(conn, "SELECT COUNT(*) FROM redirect"),
+        queued: count_state(conn, QUEUED),
+        in_flight: count_state(conn, IN_FLIGHT),
+        done: count_state(conn, DONE),
+        failed: count_state(conn, FAILED),
+        max_depth: count(conn, "SELECT IFNULL(MAX(depth), 0) FROM frontier"),
+    }
+}
+
+pub fn print_stats(stats: &Stats) {
+    println!("Pages: {}", stats.pages);
+    println!("Crawled Pages: {}", stats.crawled_pages);
+    println!("Links: {}", stats.links);
+    println!("Redirects: {}", stats.redirects);
+    println!("Frontier: {} queued, {} in flight, {} done, {} failed", stats.queued, stats.in_flight, stats.done, stats.failed);
+    println!("Max Depth: {}", stats.max_depth);
+}
+
+fn count(conn: &Connection, sql: &str) -> i64 {
+    conn.query_row(sql, &[], |row| row.get(0)).unwrap()
+}
+
+pub fn count_state(conn: &Connection, state: &str) -> i64 {
+    conn.query_row("SELECT COUNT(*) FROM frontier WHERE state = ?1", &[&state], |row| row.get(0)).unwrap()
+}
@@ -0,0 +1,253 @@
+use stats::Stats;
+
+// What the path finder reads, from a store or from a graph compiled to be
+// read only. Pages are named by the graph's own page id.
+pub trait GraphView {
+    // The page id of a crawled page, by title or ext page id. A redirect
+    // stands for its target, since that is where a racer ends up.
+    fn find_crawled_page(&self, title_or_ext_page_id: &str) -> Option<i64>;
+
+    // The pages a page links to, each with the redirect the link went
+    // through.
+    fn links_from(&self, page_id: i64) -> Vec<(i64, Option<i64>)>;
+
+    // The pages linking to a page, each with the redirect the link went
+    // through.
+    fn links_to(&self, page_id: i64) -> Vec<(i64, Option<i64>)>;
+
+    // The title of a page, or its ext page id while it has none.
+    fn page_label(&self, page_id: i64) -> String;
+
+    fn redirect_title(&self, redirect_page_id: i64) -> String;
+}
+
+// Where the graph is kept. The crawl stages write through it and the path
+// finder reads from it, on a database file or in memory alike. Pages are
+// named by the ext page id the API knows them by when written.
+pub trait GraphStore: GraphView {
+    // Adds the page unless it is known. A title marks it crawled.
+    fn upsert_page(&mut self, title_opt: Option<String>, ext_page_id: i64);
+
+    // Adds a link between two known pages. A link to a redirect is stored
+    // as a link to its target, remembering the redirect so a path can still
+    // name the link a racer has to click. Links already stored are left as
+    // they are.
+    fn add_link(&mut self, to_ext_page_id: i64, from_ext_page_id: i64, position_opt: Option<i64>);
+
+    fn add_redirect(&mut self, ext_page_id: i64, title: String, target_ext_page_id: i64);
+
+    // The ext page id a redirect leads to, `None` for pages that are none.
+    fn redirect_target(&self, ext_page_id: i64) -> Option<i64>;
+
+    // Queues a page at depth 0, again if it had failed.
+    fn queue_seed(&mut self, ext_page_id: i64);
+
+    // Queues a page found next to a crawled one, one step further from the
+    // seeds. Pages the frontier already knows are left as they are.
+    fn queue_discovered(&mut self, crawled_ext_page_id: i64, discovered_ext_page_id: i64);
+
+    // Hands out the shallowest queued pages, up to `max_in_flight` at once,
+    // and marks them in flight.
+    fn take_queued(&mut self, max_in_flight: usize) -> Vec<i64>;
+
+    // Queues pages left in flight by a crawl that never finished them.
+    fn reclaim_in_flight(&mut self);
+
+    // Queues a page again, or fails it once it has had its attempts.
+    fn requeue_or_fail(&mut self, ext_page_id: i64);
+
+    fn mark_failed(&mut self, ext_page_id: i64);
+
+    fn stats(&self) -> Stats;
+
+    // Runs `write` as one unit, so a packet is stored whole or not at all.
+    fn atomically<F: FnOnce(&mut Self)>(&mut self, write: F)
+        where Self: Sized
+    {
+        write(self)
+    }
+}
+
+// Every store has to behave the same, so each case is run against all of them.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontier::MAX_ATTEMPTS;
+    use memory_store::MemoryStore;
+    use solver::{PathResult, find_path};
+    use sqlite_store::SqliteStore;
+    use utils::open_or_create_database;
+
+    fn sqlite_store() -> SqliteStore {
+        SqliteStore::new(open_or_create_database(":memory:").unwrap())
+    }
+
+    fn check_upsert_marks_titled_pages_done<S: GraphStore>(mut store: S) {
+        store.upsert_page(None, 10);
+        assert_eq!(store.find_crawled_page("10"), None);
+        assert_eq!((store.stats().pages, store.stats().done), (1, 0));
+
+        store.upsert_page(Some("Albert Einstein".to_string()), 10);
+        let page_id = store.find_crawled_page("Albert Einstein").unwrap();
+        assert_eq!(store.find_crawled_page("10"), Some(page_id));
+        assert_eq!(store.page_label(page_id), "Albert Einstein");
+
+        // Seen again as the end of a link, the page keeps its title.
+        store.upsert_page(None, 10);
+        assert_eq!(store.page_label(page_id), "Albert Einstein");
+        let stats = store.stats();
+        assert_eq!((stats.pages, stats.done, stats.queued), (1, 1, 0));
+    }
+
+    #[test]
+    fn upsert_marks_titled_pages_done() {
+        check_upsert_marks_titled_pages_done(MemoryStore::new());
+        check_upsert_marks_titled_pages_done(sqlite_store());
+    }
+
+    fn check_add_link_resolves_redirects<S: GraphStore>(mut store: S) {
+        store.upsert_page(Some("Albert Einstein".to_string()), 1);
+        store.upsert_page(None, 2);
+        store.upsert_page(Some("Physics".to_string()), 3);
+        store.add_redirect(2, "Einstein".to_string(), 1);
+
+        store.add_link(2, 3, Some(0));
+        // The same ends again, directly and through the redirect, and a link of the target to itself.
+        store.add_link(1, 3, Some(1));
+        store.add_link(2, 3, Some(2));
+        store.add_link(2, 1, None);
+        // Links to pages that were never stored are dropped.
+        store.add_link(4, 3, None);
+
+        let einstein = store.find_crawled_page("Albert Einstein").unwrap();
+        let physics = store.find_crawled_page("Physics").unwrap();
+        let links = store.links_from(physics);
+        assert_eq!(links.len(), 1);
+        let (to_page_id, redirect_page_id_opt) = links[0];
+        assert_eq!(to_page_id, einstein);
+        assert_eq!(store.links_to(einstein), vec![(physics, redirect_page_id_opt)]);
+        assert!(store.links_from(einstein).is_empty());
+
+        let redirect_page_id = redirect_page_id_opt.unwrap();
+        assert_eq!(store.redirect_title(redirect_page_id), "Einstein");
+        assert_eq!(store.page_label(redirect_page_id), "2");
+        assert_eq!((store.redirect_target(2), store.redirect_target(1)), (Some(1), None));
+
+        // Asking for the redirect lands on its target.
+        assert_eq!(store.find_crawled_page("Einstein"), Some(einstein));
+        assert_eq!(store.find_crawled_page("2"), Some(einstein));
+
+        let stats = store.stats();
+        assert_eq!((stats.links, stats.redirects), (1, 1));
+    }
+
+    #[test]
+    fn add_link_resolves_redirects() {
+        check_add_link_resolves_redirects(MemoryStore::new());
+        check_add_link_resolves_redirects(sqlite_store());
+    }
+
+    fn check_take_queued_by_depth_and_limit<S: GraphStore>(mut store: S) {
+        store.queue_seed(1);
+        assert_eq!(store.take_queued(10), vec![1]);
+        store.upsert_page(Some("Albert Einstein".to_string()), 1);
+
+        store.upsert_page(None, 5);
+        store.upsert_page(None, 4);
+        store.queue_discovered(1, 5);
+        store.queue_discovered(1, 4);
+        store.upsert_page(Some("Physics".to_string()), 5);
+        store.upsert_page(None, 6);
+        store.queue_discovered(5, 6);
+        // Already on the frontier, so it stays at the depth it was found at.
+        store.queue_discovered(5, 4);
+        store.queue_seed(9);
+
+        assert_eq!(store.stats().max_depth, 2);
+
+        // Shallowest first, then in the order the pages were stored.
+        assert_eq!(store.take_queued(2), vec![9, 4]);
+        assert_eq!(store.take_queued(2), Vec::<i64>::new());
+        assert_eq!(store.take_queued(3), vec![6]);
+        assert_eq!(store.take_queued(10), Vec::<i64>::new());
+
+        let stats = store.stats();
+        assert_eq!((stats.queued, stats.in_flight, stats.done), (0, 3, 2));
+    }
+
+    #[test]
+    fn take_queued_by_depth_and_limit() {
+        check_take_queued_by_depth_and_limit(MemoryStore::new());
+        check_take_queued_by_depth_and_limit(sqlite_store());
+    }
+
+    fn check_requeue_or_fail_after_max_attempts<S: GraphStore>(mut store: S) {
+        store.queue_seed(1);
+
+        for attempt in 0..MAX_ATTEMPTS {
+            assert_eq!(store.take_queued(1), vec![1], "attempt {}", attempt);
+            if attempt == 0 {
+                // A crawl that died with the page in flight costs it an attempt too.
+                store.reclaim_in_flight();
+            } else {
+                store.requeue_or_fail(1);
+            }
+        }
+
+        let stats = store.stats();
+        assert_eq!((stats.queued, stats.in_flight, stats.failed), (0, 0, 1));
+        assert_eq!(store.take_queued(1), Vec::<i64>::new());
+
+        // Seeding a failed page gives it its attempts back.
+        store.queue_seed(1);
+        assert_eq!(store.take_queued(1), vec![1]);
+        store.requeue_or_fail(1);
+        assert_eq!(store.stats().queued, 1);
+
+        store.mark_failed(1);
+        assert_eq!((store.stats().queued, store.stats().failed), (0, 1));
+    }
+
+    #[test]
+    fn requeue_or_fail_after_max_attempts() {
+        check_requeue_or_fail_after_max_attempts(MemoryStore::new());
+        check_requeue_or_fail_after_max_attempts(sqlite_store());
+    }
+
+    fn check_find_path_through_redirect<S: GraphStore>(mut store: S) {
+        for &(title_opt, ext_page_id) in &[(Some("A"), 1), (None, 2), (Some("B"), 3), (Some("C"), 4), (Some("D"), 5)] {
+            store.upsert_page(title_opt.map(str::to_string), ext_page_id);
+        }
+        store.add_redirect(2, "R".to_string(), 3);
+        store.add_link(2, 1, None);
+        store.add_link(4, 3, None);
+        store.add_link(1, 5, None);
+
+        let path_labels = |result: PathResult| match result {
+            PathResult::Found(steps) => steps.iter().map(|step| step.to_string()).collect::<Vec<String>>(),
+            other => panic!("Expected a path, got {:?}", other),
+        };
+
+        assert_eq!(path_labels(find_path(&store, "A", "C")), vec!["A", "R (redirect) -> B", "C"]);
+        assert_eq!(path_labels(find_path(&store, "D", "R")), vec!["D", "A", "R (redirect) -> B"]);
+        assert_eq!(path_labels(find_path(&store, "C", "C")), vec!["C"]);
+        match find_path(&store, "C", "A") {
+            PathResult::NoPath => {}
+            other => panic!("Expected no path, got {:?}", other),
+        }
+        match find_path(&store, "A", "2") {
+            PathResult::Found(ref steps) if steps.last().map(|step| step.title.as_str()) == Some("B") => {}
+            other => panic!("Expected a path to B, got {:?}", other),
+        }
+        match find_path(&store, "A", "Nowhere") {
+            PathResult::Unknown(ref title) if title == "Nowhere" => {}
+            other => panic!("Expected an unknown page, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn find_path_through_redirect() {
+        check_find_path_through_redirect(MemoryStore::new());
+        check_find_path_through_redirect(sqlite_store());
+    }
+}
@@ -0,0 +1,345 @@
+use api_error::check_response;
+use json;
+use std::cmp;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use transport::Transport;
+use utils::WikiRacerError;
+
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+pub const DEFAULT_MAXLAG: u64 = 5;
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+
+const BASE_BACKOFF_MILLIS: u64 = 500;
+const MAX_BACKOFF_MILLIS: u64 = 60000;
+
+// Where the rate limiter reads the time and waits, so tests can run the
+// schedule without sleeping.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// Spaces requests out to at most a given rate. Clones share the same
+// schedule, so every thread holding one counts against the same limit.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    state: Arc<Mutex<RateLimiterState>>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug)]
+struct RateLimiterState {
+    interval_opt: Option<Duration>,
+    next: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> RateLimiter {
+        RateLimiter::with_clock(requests_per_second, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(requests_per_second: f64, clock: Arc<dyn Clock>) -> RateLimiter {
+        RateLimiter {
+            state: Arc::new(Mutex::new(RateLimiterState {
+                interval_opt: interval(requests_per_second),
+                next: clock.now(),
+            })),
+            clock,
+        }
+    }
+
+    // The slot is reserved while holding the lock but slept on outside of it,
+    // so waiting threads queue up one interval apart.
+    pub fn wait(&self) {
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+            let slot = cmp::max(state.next, self.clock.now());
+            if let Some(interval) = state.interval_opt {
+                state.next = slot + interval;
+            }
+            slot
+        };
+
+        let now = self.clock.now();
+        if slot > now {
+            self.clock.sleep(slot - now);
+        }
+    }
+
+    // Takes effect from the request after the one already scheduled.
+    pub fn set_rate(&self, requests_per_second: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.interval_opt = interval(requests_per_second);
+    }
+
+    // Pushes every thread's next request back, used when the server asks us to.
+    fn pause(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.next = cmp::max(state.next, self.clock.now() + duration);
+    }
+}
+
+// A rate of zero or less turns the limit off.
+fn interval(requests_per_second: f64) -> Option<Duration> {
+    if requests_per_second > 0.0 {
+        Some(Duration::from_secs_f64(1.0 / requests_per_second))
+    } else {
+        None
+    }
+}
+
+// Wraps a transport with a rate limit, `maxlag` and retries with
+// exponential backoff. Only the last failure is returned to the caller.
+pub struct ThrottledTransport<T: Transport> {
+    inner: T,
+    rate_limiter: RateLimiter,
+    maxlag: u64,
+    max_retries: u32,
+}
+
+impl<T: Transport> ThrottledTransport<T> {
+    pub fn new(inner: T, rate_limiter: RateLimiter, maxlag: u64, max_retries: u32) -> ThrottledTransport<T> {
+        ThrottledTransport {
+            inner,
+            rate_limiter,
+            maxlag,
+            max_retries,
+        }
+    }
+
+    fn fetch_once(&self, query: &str) -> Result<String, WikiRacerError> {
+        self.rate_limiter.wait();
+
+        let body = self.inner.fetch(query)?;
+
+        // maxlag and ratelimited come back as an ordinary response with an
+        // error code, so they have to be picked out of the body to be retried.
+        if body.contains("\"error\"") {
+            if let Ok(base) = json::parse(&body) {
+                match check_response(&base) {
+                    Err(err @ WikiRacerError::MaxLag(_)) |
+                    Err(err @ WikiRacerError::RateLimited(_)) => return Err(err),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(body)
+    }
+}
+
+impl<T: Transport> Transport for ThrottledTransport<T> {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+        let query = if self.maxlag > 0 { format!("{}&maxlag={}", query, self.maxlag) } else { query.to_string() };
+
+        let mut attempt = 0;
+        loop {
+            let err = match self.fetch_once(&query) {
+                Ok(body) => return Ok(body),
+                Err(err) => err,
+            };
+
+            let retry_after_opt = match err {
+                WikiRacerError::TransportError(_) => None,
+                WikiRacerError::HttpError(429, retry_after_opt) |
+                WikiRacerError::HttpError(503, retry_after_opt) => retry_after_opt,
+                WikiRacerError::HttpError(status, _) if status >= 500 => None,
+                WikiRacerError::MaxLag(lag_opt) => Some(lag_opt.unwrap_or(self.maxlag)),
+                WikiRacerError::RateLimited(_) => None,
+                _ => return Err(err),
+            };
+
+            if attempt >= self.max_retries {
+                return Err(err);
+            }
+
+            let backoff = cmp::min(BASE_BACKOFF_MILLIS << cmp::min(attempt, 16), MAX_BACKOFF_MILLIS);
+            let delay = cmp::max(Duration::from_millis(backoff), Duration::from_secs(retry_after_opt.unwrap_or(0)));
+            println!("Query failed with {}, retrying in {:?}", err, delay);
+
+            self.rate_limiter.pause(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Time only moves when slept on, and every sleep is written down.
+    #[derive(Debug)]
+    struct FakeClock {
+        start: Instant,
+        sleeps: Mutex<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Arc<FakeClock> {
+            Arc::new(FakeClock {
+                start: Instant::now(),
+                sleeps: Mutex::new(vec![]),
+            })
+        }
+
+        fn sleeps(&self) -> Vec<Duration> {
+            self.sleeps.lock().unwrap().clone()
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + self.sleeps.lock().unwrap().iter().sum::<Duration>()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.lock().unwrap().push(duration);
+        }
+    }
+
+    // Answers each fetch with the next scripted result and keeps the queries.
+    struct ScriptedTransport {
+        results: Mutex<VecDeque<Result<String, WikiRacerError>>>,
+        queries: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedTransport {
+        fn new(results: Vec<Result<String, WikiRacerError>>) -> ScriptedTransport {
+            ScriptedTransport {
+                results: Mutex::new(results.into_iter().collect()),
+                queries: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl Transport for ScriptedTransport {
+        fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+            self.queries.lock().unwrap().push(query.to_string());
+            self.results.lock().unwrap().pop_front().expect("Fetched more often than scripted")
+        }
+    }
+
+    const BODY: &str = r#"{"batchcomplete":""}"#;
+
+    fn api_error(code: &str, lag: u64) -> Result<String, WikiRacerError> {
+        Ok(format!(r#"{{"error":{{"code":"{}","info":"Slow down","lag":{}}}}}"#, code, lag))
+    }
+
+    // Runs one fetch without a rate limit and returns its result, the number
+    // of attempts and the delays slept between them.
+    fn fetch(results: Vec<Result<String, WikiRacerError>>, max_retries: u32) -> (Result<String, WikiRacerError>, usize, Vec<Duration>) {
+        let clock = FakeClock::new();
+        let transport = ThrottledTransport::new(ScriptedTransport::new(results), RateLimiter::with_clock(0.0, clock.clone()), 0, max_retries);
+        let result = transport.fetch("action=query");
+        let attempts = transport.inner.queries.lock().unwrap().len();
+        (result, attempts, clock.sleeps())
+    }
+
+    fn millis(delays: &[u64]) -> Vec<Duration> {
+        delays.iter().map(|&delay| Duration::from_millis(delay)).collect()
+    }
+
+    #[test]
+    fn retries_server_errors_with_backoff() {
+        let (result, attempts, sleeps) = fetch(vec![Err(WikiRacerError::HttpError(503, None)),
+                                                    Err(WikiRacerError::HttpError(500, None)),
+                                                    Err(WikiRacerError::HttpError(429, None)),
+                                                    Err(WikiRacerError::TransportError("Connection reset".to_string())),
+                                                    Ok(BODY.to_string())],
+                                               DEFAULT_MAX_RETRIES);
+
+        assert_eq!(result.unwrap(), BODY);
+        assert_eq!(attempts, 5);
+        assert_eq!(sleeps, millis(&[500, 1000, 2000, 4000]));
+    }
+
+    #[test]
+    fn retries_maxlag_and_ratelimited_responses() {
+        let (result, attempts, sleeps) = fetch(vec![api_error("maxlag", 7), api_error("ratelimited", 0), Ok(BODY.to_string())], DEFAULT_MAX_RETRIES);
+
+        assert_eq!(result.unwrap(), BODY);
+        assert_eq!(attempts, 3);
+        // The reported lag is waited out when longer than the backoff.
+        assert_eq!(sleeps, millis(&[7000, 1000]));
+    }
+
+    #[test]
+    fn waits_at_least_retry_after() {
+        let (result, attempts, sleeps) = fetch(vec![Err(WikiRacerError::HttpError(429, Some(3))), Err(WikiRacerError::HttpError(503, Some(0))), Ok(BODY.to_string())], DEFAULT_MAX_RETRIES);
+
+        assert_eq!(result.unwrap(), BODY);
+        assert_eq!(attempts, 3);
+        assert_eq!(sleeps, millis(&[3000, 1000]));
+    }
+
+    #[test]
+    fn caps_backoff_and_gives_up_after_the_last_attempt() {
+        let (result, attempts, sleeps) = fetch((0..10).map(|_| Err(WikiRacerError::HttpError(502, None))).collect(), 9);
+
+        match result {
+            Err(WikiRacerError::HttpError(502, None)) => {}
+            other => panic!("Expected the last error, got {:?}", other),
+        }
+        assert_eq!(attempts, 10);
+        assert_eq!(sleeps, millis(&[500, 1000, 2000, 4000, 8000, 16000, 32000, 60000, 60000]));
+    }
+
+    #[test]
+    fn returns_other_errors_at_once() {
+        let (result, attempts, sleeps) = fetch(vec![Err(WikiRacerError::HttpError(404, None)), Ok(BODY.to_string())], DEFAULT_MAX_RETRIES);
+
+        match result {
+            Err(WikiRacerError::HttpError(404, None)) => {}
+            other => panic!("Expected the 404, got {:?}", other),
+        }
+        assert_eq!(attempts, 1);
+        assert!(sleeps.is_empty());
+
+        // Other API errors are the caller's to handle, so the body is passed on.
+        let (result, attempts, _) = fetch(vec![api_error("badcontinue", 0)], DEFAULT_MAX_RETRIES);
+        assert!(result.unwrap().contains("badcontinue"));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn adds_maxlag_to_queries() {
+        let transport = ThrottledTransport::new(ScriptedTransport::new(vec![Ok(BODY.to_string())]), RateLimiter::with_clock(0.0, FakeClock::new()), DEFAULT_MAXLAG, 0);
+        transport.fetch("action=query").unwrap();
+
+        assert_eq!(*transport.inner.queries.lock().unwrap(), vec!["action=query&maxlag=5".to_string()]);
+    }
+
+    #[test]
+    fn spaces_requests_to_the_rate() {
+        let clock = FakeClock::new();
+        let rate_limiter = RateLimiter::with_clock(4.0, clock.clone());
+        for _ in 0..3 {
+            rate_limiter.wait();
+        }
+        assert_eq!(clock.sleeps(), millis(&[250, 250]));
+
+        // A pause pushes back the next slot, a slower rate the one after it.
+        rate_limiter.pause(Duration::from_secs(2));
+        rate_limiter.set_rate(1.0);
+        rate_limiter.wait();
+        rate_limiter.wait();
+        assert_eq!(clock.sleeps(), millis(&[250, 250, 2000, 1000]));
+    }
+}
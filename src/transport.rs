@@ -0,0 +1,188 @@
+use api_error::{check_response, get_warnings};
+use hyper::client::Client;
+use hyper::net::HttpsConnector;
+use hyper_native_tls::NativeTlsClient;
+use json::{self, JsonValue};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use url::form_urlencoded;
+use url::percent_encoding::percent_decode;
+use utils::WikiRacerError;
+
+pub const WIKIPEDIA_API_URL: &str = "https://en.wikipedia.org/w/api.php";
+
+pub const FIXTURE_EXTENSION: &str = "fixture";
+
+// Fetches the body of an `api.php` query, given everything after the `?`.
+pub trait Transport {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+        (**self).fetch(query)
+    }
+}
+
+pub fn get_json<T: Transport + ?Sized>(transport: &T, query: &str) -> Result<JsonValue, WikiRacerError> {
+    let string = transport.fetch(query)?;
+    // println!("{:?}", string);
+    let base = json::parse(&string).map_err(|err| WikiRacerError::JsonError(err.to_string()))?;
+
+    check_response(&base)?;
+    for warning in get_warnings(&base) {
+        println!("API warning for {}: {}", query, warning);
+    }
+
+    Ok(base)
+}
+
+pub fn encode_query(params: &[(&str, &str)]) -> String {
+    form_urlencoded::Serializer::new(String::new()).extend_pairs(params).finish()
+}
+
+pub struct HttpTransport {
+    client: Client,
+    api_url: String,
+}
+
+impl HttpTransport {
+    pub fn new(api_url: &str) -> HttpTransport {
+        let client = if api_url.starts_with("https:") {
+            let ssl = NativeTlsClient::new().unwrap();
+            let connector = HttpsConnector::new(ssl);
+            Client::with_connector(connector)
+        } else {
+            Client::new()
+        };
+
+        HttpTransport {
+            client,
+            api_url: api_url.to_string(),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+        let url = format!("{}?{}", self.api_url, query);
+        // println!("{:?}", url);
+        let mut resp = self.client
+            .get(url.as_str())
+            .send()
+            .map_err(|err| WikiRacerError::TransportError(err.to_string()))?;
+
+        if !resp.status.is_success() {
+            let retry_after_opt = resp.headers
+                .get_raw("Retry-After")
+                .and_then(|values| values.first())
+                .and_then(|value| String::from_utf8_lossy(value).trim().parse().ok());
+            return Err(WikiRacerError::HttpError(resp.status.to_u16(), retry_after_opt));
+        }
+
+        let mut body = vec![];
+        resp.read_to_end(&mut body).map_err(|err| WikiRacerError::TransportError(err.to_string()))?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+// Answers queries from saved responses instead of the network. A fixture file
+// holds the query on its first line and the response after it. Queries are
+// compared percent-decoded, whichever characters the recorder escaped.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureTransport {
+    responses: HashMap<String, String>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> FixtureTransport {
+        FixtureTransport::default()
+    }
+
+    pub fn load(dir: &Path) -> io::Result<FixtureTransport> {
+        let mut fixtures = FixtureTransport::new();
+
+        for entry_result in fs::read_dir(dir)? {
+            let path = entry_result?.path();
+            if path.extension().is_some_and(|extension| extension == FIXTURE_EXTENSION) {
+                let mut contents = String::new();
+                File::open(&path)?.read_to_string(&mut contents)?;
+
+                let mut parts = contents.splitn(2, '\n');
+                if let (Some(query), Some(body)) = (parts.next(), parts.next()) {
+                    fixtures.insert(query, body.to_string());
+                }
+            }
+        }
+
+        Ok(fixtures)
+    }
+
+    pub fn insert(&mut self, query: &str, body: String) {
+        self.responses.insert(normalize_query(query), body);
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+        match self.responses.get(&normalize_query(query)) {
+            Some(body) => Ok(body.clone()),
+            None => Err(WikiRacerError::TransportError(format!("No fixture for query {}", query))),
+        }
+    }
+}
+
+fn normalize_query(query: &str) -> String {
+    percent_decode(query.replace('+', " ").as_bytes()).decode_utf8_lossy().into_owned()
+}
+
+// Passes queries through to another transport and saves every response as a
+// fixture that `FixtureTransport::load` can replay.
+pub struct RecordingTransport<T: Transport> {
+    inner: T,
+    dir: PathBuf,
+    count: Mutex<usize>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new(inner: T, dir: &Path) -> io::Result<RecordingTransport<T>> {
+        fs::create_dir_all(dir)?;
+        let count = fs::read_dir(dir)?.count();
+
+        Ok(RecordingTransport {
+            inner,
+            dir: dir.to_path_buf(),
+            count: Mutex::new(count),
+        })
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn fetch(&self, query: &str) -> Result<String, WikiRacerError> {
+        let body = self.inner.fetch(query)?;
+
+        // Other recorders may share the directory, so names already taken
+        // by them are skipped rather than overwritten.
+        let mut count = self.count.lock().unwrap();
+        let mut file = loop {
+            let path = self.dir.join(format!("{:06}.{}", *count, FIXTURE_EXTENSION));
+            *count += 1;
+
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(file) => break file,
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(WikiRacerError::TransportError(err.to_string())),
+            }
+        };
+        write!(file, "{}\n{}", query, body).map_err(|err| WikiRacerError::TransportError(err.to_string()))?;
+
+        Ok(body)
+    }
+}
@@ -1,35 +1,57 @@
 use rusqlite::Connection;
+use schema::migrate;
+use std::error::Error;
+use std::fmt;
 
-pub fn create_database() -> Connection {
-    let conn = open_database();
+pub const DEFAULT_DATABASE_PATH: &str = "database.sqlite3";
 
-    conn.execute("CREATE TABLE page (
-                    page_id         INTEGER PRIMARY KEY AUTOINCREMENT,
-                    title           TEXT,
-                    ext_page_id     TEXT
-                )",
-                 &[])
-        .unwrap();
-
-    conn.execute("CREATE TABLE link (
-                    link_id         INTEGER PRIMARY KEY AUTOINCREMENT,
-                    to_page_id      INTEGER NOT NULL,
-                    from_page_id    INTEGER NOT NULL,
-                    length          INTEGER NOT NULL,
-                    CONSTRAINT Link_FK1 FOREIGN KEY (to_page_id) REFERENCES page(page_id),
-                    CONSTRAINT Link_FK2 FOREIGN KEY (from_page_id) REFERENCES page(page_id)
-                )",
-                 &[])
-        .unwrap();
-
-    conn
+pub fn open_database(path: &str) -> Connection {
+    Connection::open(path).unwrap()
 }
 
-pub fn open_database() -> Connection {
-    Connection::open("database.sqlite3").unwrap()
+// Creates the file when there is none, and brings its schema up to date.
+pub fn open_or_create_database(path: &str) -> Result<Connection, WikiRacerError> {
+    let mut conn = open_database(path);
+    migrate(&mut conn)?;
+    Ok(conn)
 }
 
 #[derive(Debug)]
 pub enum WikiRacerError {
     FormatError(&'static str),
+    TransportError(String),
+    IoError(String),
+    HttpError(u16, Option<u64>),
+    MaxLag(Option<u64>),
+    JsonError(String),
+    RateLimited(String),
+    BadContinue(String),
+    InvalidPageId(String),
+    MissingPage(String),
+    ApiError(String, String),
+    // The database is at the first version, this build only knows the second.
+    SchemaTooNew(i64, i64),
 }
+
+impl fmt::Display for WikiRacerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WikiRacerError::FormatError(message) => write!(f, "{}", message),
+            WikiRacerError::TransportError(ref message) => write!(f, "Request failed: {}", message),
+            WikiRacerError::IoError(ref message) => write!(f, "{}", message),
+            WikiRacerError::HttpError(status, Some(retry_after)) => write!(f, "HTTP status {}, retry after {} seconds", status, retry_after),
+            WikiRacerError::HttpError(status, None) => write!(f, "HTTP status {}", status),
+            WikiRacerError::MaxLag(Some(lag)) => write!(f, "Database replicas are lagging by {} seconds", lag),
+            WikiRacerError::MaxLag(None) => write!(f, "Database replicas are lagging"),
+            WikiRacerError::JsonError(ref message) => write!(f, "Response was not JSON: {}", message),
+            WikiRacerError::RateLimited(ref info) => write!(f, "Rate limited: {}", info),
+            WikiRacerError::BadContinue(ref info) => write!(f, "Continuation was rejected: {}", info),
+            WikiRacerError::InvalidPageId(ref info) => write!(f, "Invalid page id: {}", info),
+            WikiRacerError::MissingPage(ref key) => write!(f, "Page {} is missing", key),
+            WikiRacerError::ApiError(ref code, ref info) => write!(f, "API error {}: {}", code, info),
+            WikiRacerError::SchemaTooNew(version, known_version) => write!(f, "Schema version {} is newer than the {} this build knows", version, known_version),
+        }
+    }
+}
+
+impl Error for WikiRacerError {}
@@ -0,0 +1,187 @@
+use xml_dump::SiteInfo;
+
+const IGNORED_SPANS: &[(&str, &str)] = &[("<!--", "-->"), ("<nowiki>", "</nowiki>")];
+
+// Prefixes that lead to another wiki rather than a page of this one. Dumps
+// do not carry the interwiki table, so these are the language editions of
+// Wikipedia and the sister projects, which no article title can start with.
+const LANGUAGE_PREFIXES: &[&str] =
+    &["aa", "ab", "ace", "ady", "af", "ak", "als", "am", "an", "ang", "ar", "arc", "arz", "as", "ast", "av", "ay", "az", "azb", "ba", "bar", "bat-smg", "bcl", "be", "be-tarask", "be-x-old", "bg",
+      "bh", "bi", "bjn", "bm", "bn", "bo", "bpy", "br", "bs", "bug", "bxr", "ca", "cbk-zam", "cdo", "ce", "ceb", "ch", "cho", "chr", "chy", "ckb", "co", "cr", "crh", "cs", "csb", "cu", "cv", "cy",
+      "da", "de", "diq", "dsb", "dty", "dv", "dz", "ee", "el", "eml", "en", "eo", "es", "et", "eu", "ext", "fa", "ff", "fi", "fiu-vro", "fj", "fo", "fr", "frp", "frr", "fur", "fy", "ga", "gag",
+      "gan", "gd", "gl", "glk", "gn", "gom", "got", "gu", "gv", "ha", "hak", "haw", "he", "hi", "hif", "ho", "hr", "hsb", "ht", "hu", "hy", "hz", "ia", "id", "ie", "ig", "ii", "ik", "ilo", "inh",
+      "io", "is", "it", "iu", "ja", "jam", "jbo", "jv", "ka", "kaa", "kab", "kbd", "kg", "ki", "kj", "kk", "kl", "km", "kn", "ko", "koi", "kr", "krc", "ks", "ksh", "ku", "kv", "kw", "ky", "la",
+      "lad", "lb", "lbe", "lez", "lg", "li", "lij", "lmo", "ln", "lo", "lrc", "lt", "ltg", "lv", "mai", "map-bms", "mdf", "mg", "mh", "mhr", "mi", "min", "mk", "ml", "mn", "mo", "mr", "mrj", "ms",
+      "mt", "mus", "mwl", "my", "myv", "mzn", "na", "nah", "nap", "nds", "nds-nl", "ne", "new", "ng", "nl", "nn", "no", "nov", "nrm", "nso", "nv", "ny", "oc", "olo", "om", "or", "os", "pa", "pag",
+      "pam", "pap", "pcd", "pdc", "pfl", "pi", "pih", "pl", "pms", "pnb", "pnt", "ps", "pt", "qu", "rm", "rmy", "rn", "ro", "roa-rup", "roa-tara", "ru", "rue", "rw", "sa", "sah", "sc", "scn", "sco",
+      "sd", "se", "sg", "sh", "si", "simple", "sk", "sl", "sm", "sn", "so", "sq", "sr", "srn", "ss", "st", "stq", "su", "sv", "sw", "szl", "ta", "tcy", "te", "tet", "tg", "th", "ti", "tk", "tl", "tn",
+      "to", "tpi", "tr", "ts", "tt", "tum", "tw", "ty", "tyv", "udm", "ug", "uk", "ur", "uz", "ve", "vec", "vep", "vi", "vls", "vo", "wa", "war", "wo", "wuu", "xal", "xh", "xmf", "yi", "yo", "za",
+      "zea", "zh", "zh-classical", "zh-min-nan", "zh-yue", "zu"];
+
+const PROJECT_PREFIXES: &[&str] = &["b", "c", "commons", "d", "foundation", "incubator", "m", "meta", "metawikipedia", "mw", "mediawikiwiki", "n", "outreach", "phab", "q", "s",
+                                                    "species", "v", "voy", "w", "wikibooks", "wikidata", "wikimedia", "wikinews", "wikiquote", "wikisource", "wikispecies", "wikitech",
+                                                    "wikiversity", "wikivoyage", "wikt", "wiktionary", "wmf"];
+
+// The titles of the articles the `[[wikilinks]]` of some wikitext point at,
+// normalized and in order. Links into other namespaces or other wikis are
+// left out, and templates are not expanded.
+pub fn parse_wikilinks(text: &str, site_info: &SiteInfo) -> Vec<String> {
+    let text = strip_ignored_spans(text);
+
+    let mut titles = vec![];
+    let mut rest = text.as_str();
+
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+
+        // A caption, as in [[File:A.jpg|thumb|[[B]]]], is only skipped up to
+        // the next [[ so the links inside it are still found.
+        let end = rest.find(['|', ']', '[', '\n']).unwrap_or(rest.len());
+        match rest[end..].chars().next() {
+            Some('|') | Some(']') => (),
+            _ => continue,
+        }
+
+        if let Some(title) = normalize_link_target(&rest[..end], site_info) {
+            titles.push(title);
+        }
+    }
+
+    titles
+}
+
+// Turns a title as written into the title of the page, the way MediaWiki
+// does: underscores become spaces, runs of whitespace collapse and the first
+// letter is upper cased. Titles outside the main namespace, or on another
+// wiki, give `None`.
+pub fn normalize_title(title: &str, site_info: &SiteInfo) -> Option<String> {
+    let title = title.replace('_', " ").split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    if title.is_empty() || title.contains(['{', '}', '<', '>', '[', ']', '|']) {
+        return None;
+    }
+
+    if let Some(colon) = title.find(':') {
+        let prefix = title[..colon].trim().to_lowercase();
+        if site_info.namespaces.values().any(|namespace| namespace.to_lowercase() == prefix) || is_interwiki_prefix(&prefix) {
+            return None;
+        }
+    }
+
+    if !site_info.first_letter {
+        return Some(title);
+    }
+
+    let mut chars = title.chars();
+    chars.next().map(|first| first.to_uppercase().collect::<String>() + chars.as_str())
+}
+
+fn is_interwiki_prefix(prefix: &str) -> bool {
+    LANGUAGE_PREFIXES.contains(&prefix) || PROJECT_PREFIXES.contains(&prefix)
+}
+
+// A leading colon links to a category or file instead of embedding it, and a
+// target is the page whatever section it jumps to.
+fn normalize_link_target(target: &str, site_info: &SiteInfo) -> Option<String> {
+    let target = target.trim();
+    let target = target.strip_prefix(':').unwrap_or(target);
+    let target = match target.find('#') {
+        Some(hash) => &target[..hash],
+        None => target,
+    };
+
+    normalize_title(target, site_info)
+}
+
+// Comments and nowiki spans can hold text that looks like a link but is not
+// one. A span that is never closed runs to the end, as in MediaWiki.
+fn strip_ignored_spans(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    loop {
+        let next_span_opt = IGNORED_SPANS.iter()
+            .filter_map(|&(open, close)| rest.find(open).map(|start| (start, open, close)))
+            .min_by_key(|&(start, _, _)| start);
+
+        match next_span_opt {
+            Some((start, open, close)) => {
+                out.push_str(&rest[..start]);
+                let after_open = &rest[start + open.len()..];
+                match after_open.find(close) {
+                    Some(end) => rest = &after_open[end + close.len()..],
+                    None => return out,
+                }
+            }
+            None => {
+                out.push_str(rest);
+                return out;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn site_info(first_letter: bool) -> SiteInfo {
+        let mut namespaces = HashMap::new();
+        for &(key, name) in &[(1, "Talk"), (4, "Wikipedia"), (6, "File"), (10, "Template"), (14, "Category")] {
+            namespaces.insert(key, name.to_string());
+        }
+
+        SiteInfo {
+            namespaces,
+            first_letter,
+        }
+    }
+
+    #[test]
+    fn parses_wikilinks() {
+        let cases: &[(&str, &[&str])] = &[("[[Albert Einstein]]", &["Albert Einstein"]),
+                                          ("[[Albert Einstein|Einstein]] and [[Physics|physicist]]s", &["Albert Einstein", "Physics"]),
+                                          ("[[Albert Einstein#Early life|his youth]]", &["Albert Einstein"]),
+                                          ("[[#Early life]]", &[]),
+                                          ("[[ albert_einstein ]]", &["Albert einstein"]),
+                                          ("[[Special  relativity\t]]", &["Special relativity"]),
+                                          ("[[Category:Physicists]] [[category:Physicists]] [[:Category:Physicists]]", &[]),
+                                          ("[[Talk:Albert Einstein]] [[Wikipedia:Manual of Style]] [[Template:Infobox]]", &[]),
+                                          ("[[File:Einstein 1921.jpg|thumb|[[Albert Einstein]] in [[vienna]]]]", &["Albert Einstein", "Vienna"]),
+                                          ("[[fr:Albert Einstein]] [[FR:Albert Einstein]] [[:de:Albert Einstein]] [[simple:Albert Einstein]]", &[]),
+                                          ("[[zh-min-nan:Albert Einstein]] [[be-tarask:Альберт Эйнштэйн]]", &[]),
+                                          ("[[wikt:relativity]] [[commons:Category:Albert Einstein]] [[w:fr:Albert Einstein]] [[s:Author:Albert Einstein]]", &[]),
+                                          ("[[Star Wars: Episode IV – A New Hope]] [[Re: Zero]]", &["Star Wars: Episode IV – A New Hope", "Re: Zero"]),
+                                          ("<!-- [[Hidden]] -->[[Shown]]<nowiki>[[Escaped]]</nowiki>", &["Shown"]),
+                                          ("[[Unclosed <!-- [[Hidden]]", &[]),
+                                          ("[[Broken\nlink]]", &[]),
+                                          ("[[{{PAGENAME}}]] [[A<b>]]", &[]),
+                                          ("[[B]] [[A]] [[b]]", &["B", "A", "B"]),
+                                          ("[[éclair]] [[ängström]]", &["Éclair", "Ängström"])];
+
+        for &(text, expected) in cases {
+            assert_eq!(parse_wikilinks(text, &site_info(true)), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn normalizes_titles() {
+        let cases: &[(&str, bool, Option<&str>)] = &[("albert_Einstein", true, Some("Albert Einstein")),
+                                                     ("albert_Einstein", false, Some("albert Einstein")),
+                                                     ("iPhone", true, Some("IPhone")),
+                                                     ("iPhone", false, Some("iPhone")),
+                                                     ("  Many   __  spaces ", true, Some("Many spaces")),
+                                                     ("Category:Physicists", true, None),
+                                                     ("TALK : Albert Einstein", true, None),
+                                                     ("fr:Albert Einstein", true, None),
+                                                     ("Wiktionary:relativity", false, None),
+                                                     ("Mission: Impossible", true, Some("Mission: Impossible")),
+                                                     ("_", true, None),
+                                                     ("", true, None)];
+
+        for &(title, first_letter, expected) in cases {
+            assert_eq!(normalize_title(title, &site_info(first_letter)).as_deref(), expected, "{:?}", title);
+        }
+    }
+}
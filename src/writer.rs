@@ -1,136 +1,72 @@
 use querier_x_writer::QuerierToWriter;
-use rusqlite::{self, Transaction};
-use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::mpsc::Receiver;
 use std::thread::{self, JoinHandle};
-use utils::open_database;
+use store::GraphStore;
 
-pub fn start_writer(receiver: Receiver<QuerierToWriter>) -> JoinHandle<()> {
+// Writes what comes in to the store, and hands the store back once told to
+// exit, so one held in memory can be read afterwards.
+pub fn start_writer<S: GraphStore + Send + 'static>(store: S, receiver: Receiver<QuerierToWriter>) -> JoinHandle<S> {
     thread::spawn(move || {
         let receiver = receiver;
 
-        let mut conn = open_database();
+        let mut store = store;
 
-        let mut exit = false;
-
-        while !exit {
-            match receiver.try_recv() {
-                Ok(packet_enum) => {
-                    match packet_enum {
-                        QuerierToWriter::Exit => {
-                            exit = true;
-                        }
-                        QuerierToWriter::Packet(pages, links) => {
-                            let transaction = conn.transaction().unwrap();
-
-                            for page in pages {
-                                add_page_to_database(&transaction, page.0, page.1);
-                            }
-                            for link in links {
-                                add_link_to_database(&transaction, link.0, link.1);
-                            }
-
-                            transaction.commit().unwrap();
-                        }
-                    }
+        // Runs until told to exit, or until the querier is gone.
+        for packet_enum in receiver.iter() {
+            match packet_enum {
+                QuerierToWriter::Exit => break,
+                QuerierToWriter::Packet(pages, links, redirects) => {
+                    store.atomically(|store| write_packet(store, pages, links, redirects));
+                }
+                QuerierToWriter::Failed(ext_page_id) => {
+                    store.requeue_or_fail(ext_page_id);
+                }
+                QuerierToWriter::Missing(ext_page_id) => {
+                    store.mark_failed(ext_page_id);
+                }
+                QuerierToWriter::Seed(ext_page_id) => {
+                    store.queue_seed(ext_page_id);
+                }
+                QuerierToWriter::Flush => {
+                    println!("Flushed");
                 }
-                Err(TryRecvError::Disconnected) => {}
-                Err(TryRecvError::Empty) => {}
-            }
-        }
-    })
-}
-
-fn add_link_to_database(trans: &Transaction, to_ext_page_id: String, from_ext_page_id: String) {
-    let to_page_id_opt = get_page_id(trans, to_ext_page_id);
-    let from_page_id_opt = get_page_id(trans, from_ext_page_id);
-
-    if let Some(to_page_id) = to_page_id_opt {
-        if let Some(from_page_id) = from_page_id_opt {
-            if !has_link(trans, to_page_id, from_page_id) {
-                trans.execute("INSERT INTO link (to_page_id, from_page_id, length) VALUES (?1, ?2, 1)", &[&to_page_id, &from_page_id])
-                    .unwrap();
             }
         }
-    }
-}
 
-fn has_link(trans: &Transaction, to_page_id: i64, from_page_id: i64) -> bool {
-    let mut stmt = trans.prepare("SELECT link_id FROM link WHERE to_page_id = ?1 and from_page_id = ?2")
-        .unwrap();
-
-    let link_id_iter = stmt.query_map(&[&to_page_id, &from_page_id], |row| row.get(0)).unwrap();
-
-    for link_id_result in link_id_iter {
-        let _: i64 = link_id_result.unwrap();
-        return true;
-    }
-    false
+        store
+    })
 }
 
-fn get_page_id(trans: &Transaction, ext_page_id: String) -> Option<i64> {
-    let mut stmt = trans.prepare("SELECT page_id FROM page WHERE ext_page_id = ?1").unwrap();
-
-    let page_id_iter = stmt.query_map(&[&ext_page_id.to_string()], |row| row.get(0)).unwrap();
+fn write_packet<S: GraphStore>(store: &mut S, pages: Vec<(Option<String>, i64)>, links: Vec<(i64, i64, Option<i64>)>, redirects: Vec<(i64, String, i64)>) {
+    let crawled: Vec<i64> = pages.iter().filter(|page| page.0.is_some()).map(|page| page.1).collect();
 
-    for page_id_result in page_id_iter {
-        return Some(page_id_result.unwrap());
+    for page in pages {
+        store.upsert_page(page.0, page.1);
     }
-    None
-}
-
-fn add_page_to_database(trans: &Transaction, title_opt: Option<String>, ext_page_id: String) {
-    if let Some(title) = title_opt {
-        add_page_to_database_with_title(trans, title, ext_page_id);
-    } else {
-        add_page_to_database_without_title(trans, ext_page_id);
-    }
-}
-
-fn add_page_to_database_without_title(trans: &Transaction, ext_page_id: String) {
-    let mut stmt = trans.prepare("SELECT ext_page_id FROM page WHERE ext_page_id = ?1")
-        .unwrap();
-
-    let mut ext_page_id_iter = stmt.query_map(&[&ext_page_id.to_string()], |row| row.get(0))
-        .unwrap();
-
-    let ext_page_id_opt: Option<rusqlite::Result<String>> = ext_page_id_iter.next();
-
-    match ext_page_id_opt {
-        Some(Ok(_)) => (),
-        Some(err) => {
-            err.unwrap();
+    for redirect in &redirects {
+        if crawled.contains(&redirect.0) {
+            store.queue_discovered(redirect.0, redirect.2);
         }
-        None => {
-            trans.execute("INSERT INTO page (ext_page_id) VALUES (?1)", &[&ext_page_id.to_string()])
-                .unwrap();
+        if crawled.contains(&redirect.2) {
+            store.queue_discovered(redirect.2, redirect.0);
         }
     }
-}
-
-fn add_page_title_to_database(trans: &Transaction, title: String, ext_page_id: String) {
-    trans.execute("UPDATE page SET title = ?1 WHERE ext_page_id = ?2", &[&title.to_string(), &ext_page_id.to_string()])
-        .unwrap();
-}
-
-fn add_page_to_database_with_title(trans: &Transaction, title: String, ext_page_id: String) {
-    let mut stmt = trans.prepare("SELECT ext_page_id FROM page WHERE ext_page_id = ?1 or title = ?2")
-        .unwrap();
-
-    let mut ext_page_id_iter = stmt.query_map(&[&ext_page_id.to_string(), &title.to_string()], |row| row.get(0))
-        .unwrap();
-
-    let ext_page_id_opt: Option<rusqlite::Result<String>> = ext_page_id_iter.next();
-
-    match ext_page_id_opt {
-        Some(Ok(_)) => {
-            add_page_title_to_database(trans, title, ext_page_id);
-        }
-        Some(err) => {
-            err.unwrap();
+    for redirect in redirects {
+        store.add_redirect(redirect.0, redirect.1, redirect.2);
+    }
+    for link in &links {
+        if crawled.contains(&link.0) {
+            store.queue_discovered(link.0, link.1);
         }
-        None => {
-            trans.execute("INSERT INTO page (title, ext_page_id) VALUES (?1, ?2)", &[&title.to_string(), &ext_page_id.to_string()])
-                .unwrap();
+        if crawled.contains(&link.1) {
+            store.queue_discovered(link.1, link.0);
+            // Following the link lands on the target, so it is just as close.
+            if let Some(target_ext_page_id) = store.redirect_target(link.0) {
+                store.queue_discovered(link.1, target_ext_page_id);
+            }
         }
     }
+    for link in links {
+        store.add_link(link.0, link.1, link.2);
+    }
 }
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::io::Read;
+use utils::WikiRacerError;
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+// The namespaces of the wiki a dump was taken from, as listed in its
+// `<siteinfo>`.
+#[derive(Debug, Clone, Default)]
+pub struct SiteInfo {
+    // Names of every namespace but the main one, by key.
+    pub namespaces: HashMap<i64, String>,
+    // Whether the first letter of an article title is always upper case.
+    pub first_letter: bool,
+}
+
+// A `<page>` of a dump with the text of its last revision.
+#[derive(Debug, Clone, Default)]
+pub struct DumpPage {
+    pub page_id: i64,
+    pub namespace: i64,
+    pub title: String,
+    pub redirect_opt: Option<String>,
+    pub text: String,
+}
+
+// Streams the pages out of a `pages-articles.xml` dump one at a time.
+pub struct XmlDumpReader<R: Read> {
+    events: EventReader<R>,
+    pub site_info: SiteInfo,
+    done: bool,
+}
+
+impl<R: Read> XmlDumpReader<R> {
+    // Reads up to the end of `<siteinfo>`, which comes before any page.
+    pub fn new(input: R) -> Result<XmlDumpReader<R>, WikiRacerError> {
+        let events = ParserConfig::new().whitespace_to_characters(true).cdata_to_characters(true).create_reader(input);
+
+        let mut reader = XmlDumpReader {
+            events,
+            site_info: SiteInfo::default(),
+            done: false,
+        };
+
+        let mut namespace_opt: Option<(i64, String)> = None;
+        let mut chars = String::new();
+
+        loop {
+            match reader.next_event()? {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    chars.clear();
+                    match name.local_name.as_str() {
+                        "namespace" => {
+                            let key = attributes.iter().find(|attribute| attribute.name.local_name == "key").and_then(|attribute| attribute.value.parse().ok());
+                            let case = attributes.iter().find(|attribute| attribute.name.local_name == "case").map(|attribute| attribute.value.clone());
+                            if let Some(key) = key {
+                                if key == 0 {
+                                    reader.site_info.first_letter = case.is_none_or(|case| case == "first-letter");
+                                }
+                                namespace_opt = Some((key, String::new()));
+                            }
+                        }
+                        "page" => {
+                            return Err(WikiRacerError::FormatError("Dump had a page before its siteinfo"));
+                        }
+                        _ => (),
+                    }
+                }
+                XmlEvent::Characters(text) => chars.push_str(&text),
+                XmlEvent::EndElement { name } => {
+                    match name.local_name.as_str() {
+                        "namespace" => {
+                            if let Some((key, _)) = namespace_opt.take() {
+                                if key != 0 {
+                                    reader.site_info.namespaces.insert(key, chars.clone());
+                                }
+                            }
+                        }
+                        "siteinfo" => return Ok(reader),
+                        _ => (),
+                    }
+                }
+                XmlEvent::EndDocument => return Err(WikiRacerError::FormatError("Dump had no siteinfo")),
+                _ => (),
+            }
+        }
+    }
+
+    fn next_event(&mut self) -> Result<XmlEvent, WikiRacerError> {
+        self.events.next().map_err(|err| WikiRacerError::IoError(err.to_string()))
+    }
+
+    fn next_page(&mut self) -> Result<Option<DumpPage>, WikiRacerError> {
+        let mut page_opt: Option<DumpPage> = None;
+        let mut path: Vec<String> = vec![];
+        let mut chars = String::new();
+
+        loop {
+            match self.next_event()? {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    chars.clear();
+                    if name.local_name == "page" {
+                        page_opt = Some(DumpPage::default());
+                    }
+                    if let Some(ref mut page) = page_opt {
+                        if name.local_name == "redirect" {
+                            page.redirect_opt = attributes.into_iter().find(|attribute| attribute.name.local_name == "title").map(|attribute| attribute.value);
+                        }
+                    }
+                    path.push(name.local_name);
+                }
+                XmlEvent::Characters(text) => chars.push_str(&text),
+                XmlEvent::EndElement { name } => {
+                    path.pop();
+                    let parent = path.last().map_or("", |parent| parent.as_str());
+                    if let Some(ref mut page) = page_opt {
+                        match (parent, name.local_name.as_str()) {
+                            ("page", "title") => page.title = chars.clone(),
+                            ("page", "ns") => page.namespace = chars.trim().parse().map_err(|_| WikiRacerError::FormatError("Dump page namespace was not a number"))?,
+                            ("page", "id") => page.page_id = chars.trim().parse().map_err(|_| WikiRacerError::FormatError("Dump page id was not a number"))?,
+                            // Later revisions replace earlier ones, the last one is current.
+                            ("revision", "text") => page.text = chars.clone(),
+                            _ => (),
+                        }
+                    }
+                    if name.local_name == "page" {
+                        return Ok(page_opt);
+                    }
+                }
+                XmlEvent::EndDocument => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for XmlDumpReader<R> {
+    type Item = Result<DumpPage, WikiRacerError>;
+
+    fn next(&mut self) -> Option<Result<DumpPage, WikiRacerError>> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_page() {
+            Ok(Some(page)) => Some(Ok(page)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn read_fixture() -> XmlDumpReader<File> {
+        XmlDumpReader::new(File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/xml/pages-articles.xml")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_siteinfo_namespaces() {
+        let site_info = read_fixture().site_info;
+
+        let mut namespaces: Vec<(i64, String)> = site_info.namespaces.into_iter().collect();
+        namespaces.sort();
+        assert_eq!(namespaces,
+                   vec![(-2, "Media".to_string()), (-1, "Special".to_string()), (1, "Talk".to_string()), (10, "Template".to_string()), (14, "Category".to_string())]);
+        assert!(site_info.first_letter);
+    }
+
+    #[test]
+    fn reads_pages_with_redirects() {
+        let pages: Vec<DumpPage> = read_fixture().map(|page_result| page_result.unwrap()).collect();
+
+        assert_eq!(pages.iter().map(|page| (page.page_id, page.namespace, page.title.as_str(), page.redirect_opt.as_deref())).collect::<Vec<_>>(),
+                   vec![(10, 0, "Alpha", None), (11, 1, "Talk:Alpha", None), (12, 0, "Beta", None), (13, 0, "Delta", Some("Gamma")), (14, 0, "Gamma", None), (15, 0, "Epsilon", Some("Nowhere"))]);
+        assert_eq!(pages[2].text, "{{Template:Box}} [[Alpha]] & [[gamma]] <b>bold</b>");
+    }
+
+    #[test]
+    fn keeps_the_last_revision() {
+        let dump = r#"<mediawiki><siteinfo><namespaces><namespace key="0" case="case-sensitive" /></namespaces></siteinfo>
+                      <page><title>a</title><ns>0</ns><id>1</id>
+                      <revision><id>2</id><text>old</text></revision>
+                      <revision><id>3</id><text>new</text></revision></page></mediawiki>"#;
+        let mut reader = XmlDumpReader::new(dump.as_bytes()).unwrap();
+
+        assert!(!reader.site_info.first_letter);
+        let page = reader.next().unwrap().unwrap();
+        assert_eq!((page.page_id, page.title.as_str(), page.text.as_str()), (1, "a", "new"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn rejects_dumps_without_siteinfo() {
+        for dump in &["<mediawiki><page><title>A</title></page></mediawiki>", "<mediawiki></mediawiki>"] {
+            match XmlDumpReader::new(dump.as_bytes()) {
+                Err(WikiRacerError::FormatError(_)) => {}
+                Err(other) => panic!("Expected a format error, got {:?}", other),
+                Ok(_) => panic!("Expected a format error"),
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use bzip2::read::MultiBzDecoder;
+use querier_x_writer::QuerierToWriter;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::mem;
+use std::path::Path;
+use std::sync::mpsc::SyncSender;
+use utils::WikiRacerError;
+use wikitext::{normalize_title, parse_wikilinks};
+use xml_dump::XmlDumpReader;
+
+const ARTICLE_NAMESPACE: i64 = 0;
+
+const ROWS_PER_PACKET: usize = 10000;
+
+const PROGRESS_INTERVAL: usize = 100000;
+
+#[derive(Debug, Default)]
+pub struct XmlImportStats {
+    pub pages: usize,
+    pub redirects: usize,
+    pub links: usize,
+}
+
+// Hands the articles, redirects and wikitext links of a `pages-articles.xml`
+// dump, bzip2 compressed or not, to the writer stage. Links may name pages
+// that come later, so the file is read twice: titles first, then links.
+pub fn import_xml_dump(path: &Path, sender: &SyncSender<QuerierToWriter>) -> Result<XmlImportStats, WikiRacerError> {
+    let mut stats = XmlImportStats::default();
+
+    let mut page_ids_by_title: HashMap<String, i64> = HashMap::new();
+    let mut redirect_targets = vec![];
+
+    let reader = XmlDumpReader::new(open_xml_dump(path)?)?;
+    let site_info = reader.site_info.clone();
+
+    let mut pages = vec![];
+    for page_result in reader {
+        let page = page_result?;
+        if page.namespace != ARTICLE_NAMESPACE {
+            continue;
+        }
+
+        if let Some(redirect) = page.redirect_opt {
+            redirect_targets.push((page.page_id, page.title.clone(), redirect));
+        }
+        page_ids_by_title.insert(page.title.clone(), page.page_id);
+        pages.push((Some(page.title), page.page_id));
+
+        if pages.len() >= ROWS_PER_PACKET {
+            sender.send(QuerierToWriter::Packet(mem::take(&mut pages), vec![], vec![])).unwrap();
+        }
+
+        stats.pages += 1;
+        if stats.pages % PROGRESS_INTERVAL == 0 {
+            println!("{} pages", stats.pages);
+        }
+    }
+    sender.send(QuerierToWriter::Packet(pages, vec![], vec![])).unwrap();
+
+    let mut redirects = vec![];
+    let mut redirect_page_ids = HashSet::new();
+    for (page_id, title, target) in redirect_targets {
+        // Whether or not the target is an article, the text of a redirect is
+        // no article with links of its own.
+        redirect_page_ids.insert(page_id);
+
+        let target_title_opt = normalize_title(target.split('#').next().unwrap_or(""), &site_info);
+        if let Some(&target_page_id) = target_title_opt.and_then(|target_title| page_ids_by_title.get(&target_title)) {
+            redirects.push((page_id, title, target_page_id));
+            stats.redirects += 1;
+        }
+
+        if redirects.len() >= ROWS_PER_PACKET {
+            sender.send(QuerierToWriter::Packet(vec![], vec![], mem::take(&mut redirects))).unwrap();
+        }
+    }
+    sender.send(QuerierToWriter::Packet(vec![], vec![], redirects)).unwrap();
+
+    let mut links = vec![];
+    for page_result in XmlDumpReader::new(open_xml_dump(path)?)? {
+        let page = page_result?;
+        if page.namespace != ARTICLE_NAMESPACE || redirect_page_ids.contains(&page.page_id) {
+            continue;
+        }
+
+        let mut linked_page_ids = HashSet::new();
+        for title in parse_wikilinks(&page.text, &site_info) {
+            if let Some(&linked_page_id) = page_ids_by_title.get(&title) {
+                if linked_page_id != page.page_id && linked_page_ids.insert(linked_page_id) {
+                    let position = linked_page_ids.len() as i64 - 1;
+                    links.push((linked_page_id, page.page_id, Some(position)));
+                }
+            }
+        }
+        stats.links += linked_page_ids.len();
+
+        if links.len() >= ROWS_PER_PACKET {
+            sender.send(QuerierToWriter::Packet(vec![], mem::take(&mut links), vec![])).unwrap();
+        }
+    }
+    sender.send(QuerierToWriter::Packet(vec![], links, vec![])).unwrap();
+
+    Ok(stats)
+}
+
+fn open_xml_dump(path: &Path) -> Result<Box<dyn Read>, WikiRacerError> {
+    let file = File::open(path).map_err(|err| WikiRacerError::IoError(format!("{}: {}", path.display(), err)))?;
+
+    if path.extension().is_some_and(|extension| extension == "bz2") {
+        Ok(Box::new(BufReader::new(MultiBzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlite_store::SqliteStore;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::mpsc::sync_channel;
+    use utils::{open_database, open_or_create_database};
+    use writer::start_writer;
+
+    #[test]
+    fn imports_articles_redirects_and_links() {
+        let path = env::temp_dir().join(format!("wikiracer-xml-import-{}.sqlite3", process::id()));
+        let _ = fs::remove_file(&path);
+        let path_str = path.to_str().unwrap();
+
+        let (sender, receiver) = sync_channel::<QuerierToWriter>(8);
+        let writer = start_writer(SqliteStore::new(open_or_create_database(path_str).unwrap()), receiver);
+        let stats = import_xml_dump(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/xml/pages-articles.xml")), &sender).unwrap();
+        sender.send(QuerierToWriter::Exit).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!((stats.pages, stats.redirects, stats.links), (5, 1, 4));
+
+        let conn = open_database(path_str);
+        let rows = |sql: &str| -> Vec<String> {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let row_iter = stmt.query_map(&[], |row| row.get(0)).unwrap();
+            row_iter.map(|row_result| row_result.unwrap()).collect()
+        };
+
+        // The talk page is left out, Epsilon stays an article as its target does not exist.
+        assert_eq!(rows("SELECT ext_page_id || ' ' || title FROM page ORDER BY ext_page_id"), vec!["10 Alpha", "12 Beta", "13 Delta", "14 Gamma", "15 Epsilon"]);
+        assert_eq!(rows("SELECT page.ext_page_id || ' ' || redirect.title || ' ' || target.ext_page_id FROM redirect
+                         JOIN page ON page.page_id = redirect.page_id
+                         JOIN page AS target ON target.page_id = redirect.target_page_id"),
+                   vec!["13 Delta 14"]);
+        // Beta and Gamma come after Alpha in the dump, Nowhere is in none of it.
+        assert_eq!(rows("SELECT from_page.ext_page_id || ' ' || to_page.ext_page_id || ' ' || IFNULL(redirect_page.ext_page_id, '-') || ' ' || link.position FROM link
+                         JOIN page AS from_page ON from_page.page_id = link.from_page_id
+                         JOIN page AS to_page ON to_page.page_id = link.to_page_id
+                         LEFT JOIN page AS redirect_page ON redirect_page.page_id = link.redirect_page_id
+                         ORDER BY from_page.ext_page_id, link.position"),
+                   vec!["10 12 - 0", "10 14 13 1", "12 10 - 0", "12 14 - 1"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}